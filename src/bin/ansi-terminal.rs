@@ -0,0 +1,147 @@
+// Drive the Grove LCD from the host by printing to the ST-LINK virtual com port
+//
+// PA2 (TX) and PA3 (RX) are mapped to USART2 -> STLINK USB /dev/ttyACM?
+//
+//   $ printf '\033[2J\033[1;1HHello\n\033[41m25\xc2\xb0C' > /dev/ttyACM0
+//
+// Erasing rewrites whole rows by I2C, which takes longer than receiving a
+// byte. The USART interrupt buffers the received bytes, the main loop feeds
+// them to the terminal.
+
+#![deny(warnings)]
+#![deny(unsafe_code)]
+#![no_main]
+#![no_std]
+
+use nucleo_stm32g071rb as board;
+
+use cortex_m::interrupt::{free, Mutex};
+
+use core::cell::RefCell;
+use core::ops::DerefMut;
+
+use board::hal::{interrupt, prelude::*, serial::*, stm32};
+
+use board::lcd::{Color, RgbLCD};
+use board::ui::ansi::Terminal;
+
+/// Ring buffer of received bytes, filled by the USART interrupt
+struct RxBuffer<const N: usize> {
+    bytes: [u8; N],
+    first: usize,
+    len: usize,
+    lost: u32,
+}
+
+impl<const N: usize> RxBuffer<N> {
+    const fn new() -> Self {
+        RxBuffer {
+            bytes: [0; N],
+            first: 0,
+            len: 0,
+            lost: 0,
+        }
+    }
+
+    /// Append a byte, it is lost if the buffer is full
+    fn push(&mut self, byte: u8) {
+        if self.len == N {
+            self.lost = self.lost.wrapping_add(1);
+        } else {
+            self.bytes[(self.first + self.len) % N] = byte;
+            self.len += 1;
+        }
+    }
+
+    fn pop(&mut self) -> Option<u8> {
+        if self.len == 0 {
+            None
+        } else {
+            let byte = self.bytes[self.first];
+            self.first = (self.first + 1) % N;
+            self.len -= 1;
+            Some(byte)
+        }
+    }
+
+    /// Number of bytes lost since the last call
+    fn take_lost(&mut self) -> u32 {
+        core::mem::replace(&mut self.lost, 0)
+    }
+}
+
+static RX: Mutex<RefCell<Option<Rx<stm32::USART2, FullConfig>>>> = Mutex::new(RefCell::new(None));
+static RX_BUFFER: Mutex<RefCell<RxBuffer<256>>> = Mutex::new(RefCell::new(RxBuffer::new()));
+
+#[interrupt]
+fn USART2() {
+    free(|cs| {
+        if let Some(ref mut rx) = RX.borrow(cs).borrow_mut().deref_mut() {
+            let mut buffer = RX_BUFFER.borrow(cs).borrow_mut();
+            loop {
+                match rx.read() {
+                    Ok(byte) => buffer.push(byte),
+                    Err(nb::Error::WouldBlock) => break,
+                    // overrun, framing or noise error, the byte is gone
+                    Err(nb::Error::Other(_)) => buffer.lost = buffer.lost.wrapping_add(1),
+                }
+            }
+        }
+    });
+}
+
+#[cortex_m_rt::entry]
+fn main() -> ! {
+    let dp = stm32::Peripherals::take().expect("cannot take peripherals");
+    let mut rcc = dp.RCC.constrain();
+    let delay = dp.TIM15.delay(&mut rcc);
+
+    let gpiob = dp.GPIOB.split(&mut rcc);
+    let sda = gpiob.pb9.into_open_drain_output();
+    let scl = gpiob.pb8.into_open_drain_output();
+    let mut i2c = dp.I2C1.i2c(sda, scl, 100.khz(), &mut rcc);
+
+    let gpioa = dp.GPIOA.split(&mut rcc);
+    let usart2 = dp
+        .USART2
+        .usart(
+            gpioa.pa2,
+            gpioa.pa3,
+            FullConfig::default().baudrate(115200.bps()),
+            &mut rcc,
+        )
+        .unwrap();
+    let (_tx, mut rx) = usart2.split();
+
+    let mut lcd = RgbLCD::new();
+    lcd.init(&mut i2c, delay).unwrap();
+    let mut terminal = Terminal::new(Color::White);
+    lcd.set_color(&mut i2c, Color::White).unwrap();
+
+    rx.listen();
+    free(|cs| {
+        RX.borrow(cs).replace(Some(rx));
+    });
+
+    // Enable interrupt
+    stm32::NVIC::unpend(interrupt::USART2);
+    #[allow(unsafe_code)]
+    unsafe {
+        stm32::NVIC::unmask(interrupt::USART2);
+    }
+
+    defmt::println!("Terminal ready");
+
+    loop {
+        let (byte, lost) = free(|cs| {
+            let mut buffer = RX_BUFFER.borrow(cs).borrow_mut();
+            (buffer.pop(), buffer.take_lost())
+        });
+        if lost > 0 {
+            defmt::println!("{} bytes lost", lost);
+        }
+        if let Some(byte) = byte {
+            terminal.feed(&mut lcd, &mut i2c, byte).unwrap();
+        }
+    }
+}
@@ -0,0 +1,54 @@
+// Scan I2C1 (PB8 SCL, PB9 SDA as in lcd.rs) and report the responding devices
+//
+// The report is printed via defmt and on the ST-LINK virtual com port:
+// PA2 (TX) and PA3 (RX) are mapped to USART2 -> STLINK USB /dev/ttyACM?
+
+#![deny(warnings)]
+#![deny(unsafe_code)]
+#![no_main]
+#![no_std]
+
+use core::fmt::Write;
+
+use nucleo_stm32g071rb as board;
+
+use board::hal::{prelude::*, serial::*, stm32};
+use board::i2c::scan::{scan, FIRST_ADDRESS, LAST_ADDRESS};
+
+#[cortex_m_rt::entry]
+fn main() -> ! {
+    let dp = stm32::Peripherals::take().expect("cannot take peripherals");
+    let mut rcc = dp.RCC.constrain();
+
+    let gpiob = dp.GPIOB.split(&mut rcc);
+    let sda = gpiob.pb9.into_open_drain_output();
+    let scl = gpiob.pb8.into_open_drain_output();
+    let mut i2c = dp.I2C1.i2c(sda, scl, 100.khz(), &mut rcc);
+
+    let gpioa = dp.GPIOA.split(&mut rcc);
+    let usart2 = dp
+        .USART2
+        .usart(
+            gpioa.pa2,
+            gpioa.pa3,
+            FullConfig::default().baudrate(115200.bps()),
+            &mut rcc,
+        )
+        .unwrap();
+    let (mut tx, _rx) = usart2.split();
+
+    defmt::println!("Scan {=u8:#04x}..{=u8:#04x}", FIRST_ADDRESS, LAST_ADDRESS);
+    write!(tx, "Scan {:#04x}..{:#04x}\r\n", FIRST_ADDRESS, LAST_ADDRESS).ok();
+
+    let mut count = 0;
+    for device in scan(&mut i2c) {
+        let name = device.name.unwrap_or("unknown");
+        defmt::println!("{=u8:#04x} {=str}", device.address, name);
+        write!(tx, "{:#04x} {}\r\n", device.address, name).ok();
+        count += 1;
+    }
+
+    defmt::println!("{} devices found", count);
+    write!(tx, "{} devices found\r\n", count).ok();
+    board::exit()
+}
@@ -1,4 +1,9 @@
-// #![deny(warnings)]
+// Receive RC5, RC6, NEC and SIRC codes from the timestamps of the edges
+//
+// The receiver output on PB3 is captured by TIM2 channel 2 (AF2). The
+// interrupt only fires on edges; the main loop decodes the marks and spaces.
+
+#![deny(warnings)]
 #![no_main]
 #![no_std]
 
@@ -6,54 +11,27 @@ use nucleo_stm32g071rb as board;
 
 use cortex_m::{
     self,
-    // asm,
     interrupt::{free, Mutex},
 };
 
 use core::cell::RefCell;
 use core::ops::DerefMut;
 
-use board::hal::{
-    gpio::{gpiob::PB3, Input, PullUp},
-    interrupt,
-    prelude::*,
-    stm32,
-    timer::Timer,
-};
-
-use manchester_code::{BitOrder, Decoder, SyncOnTurningEdge, ActivityLevel};
+use board::hal::{interrupt, prelude::*, stm32};
+use board::infrared::capture::{EdgeBuffer, InputCapture, PulseStream};
+use board::infrared::{receiver::IrReceiver, IrDecoder};
 
+/// Longer than any mark or space within a frame
+const PAUSE_US: u32 = 10_000;
 
-static IR_DIODE: Mutex<RefCell<Option<PB3<Input<PullUp>>>>> = Mutex::new(RefCell::new(None));
-static DECODER: Mutex<RefCell<Decoder>> = Mutex::new(RefCell::new(Decoder::new(
-    ActivityLevel::Low,
-    SyncOnTurningEdge::Second,
-    BitOrder::BigEndian,
-)));
-static TIMER_TIM2: Mutex<RefCell<Option<Timer<stm32::TIM2>>>> = Mutex::new(RefCell::new(None));
+static CAPTURE: Mutex<RefCell<Option<InputCapture>>> = Mutex::new(RefCell::new(None));
+static EDGES: Mutex<RefCell<EdgeBuffer<128>>> = Mutex::new(RefCell::new(EdgeBuffer::new()));
 
 #[interrupt]
-unsafe fn TIM2() {
+fn TIM2() {
     free(|cs| {
-        if let Some(ref mut timer) = TIMER_TIM2.borrow(cs).borrow_mut().deref_mut() {
-            timer.clear_irq();
-        }
-
-        if let Some(ref mut ir_diode) = IR_DIODE.borrow(cs).borrow_mut().deref_mut() {
-            // let decoder = DECODER.borrow(cs).borrow_mut().deref_mut();
-            match DECODER
-                .borrow(cs)
-                .borrow_mut()
-                .deref_mut()
-                .next(ir_diode.is_high().unwrap())
-            {
-                None => (),
-                Some(t) => {
-                    if t.len() > 2 {
-                        defmt::println!("Datagram: {:?}", t);
-                    }
-                }
-            };
+        if let Some(ref mut capture) = CAPTURE.borrow(cs).borrow_mut().deref_mut() {
+            capture.read(EDGES.borrow(cs).borrow_mut().deref_mut());
         }
     });
 }
@@ -64,11 +42,14 @@ fn main() -> ! {
     let mut rcc = dp.RCC.constrain();
 
     let gpiob = dp.GPIOB.split(&mut rcc);
-    let ir_diode = gpiob.pb3.into_pull_up_input();
+    let mut capture = InputCapture::tim2(dp.TIM2, gpiob.pb3, &mut rcc);
+    capture.listen();
 
-    let mut timer = dp.TIM2.timer(&mut rcc);
-    timer.start(296.us()); // 889 µs / 4; aka 4 samples per half bit period
-    timer.listen();
+    // Move shared resources to Mutex
+    // free wraps a critical section see https://docs.rs/cortex-m/0.7.4/cortex_m/interrupt/fn.free.html
+    free(|cs| {
+        CAPTURE.borrow(cs).replace(Some(capture));
+    });
 
     // Enable interrupt
     stm32::NVIC::unpend(interrupt::TIM2);
@@ -76,16 +57,30 @@ fn main() -> ! {
         stm32::NVIC::unmask(interrupt::TIM2);
     }
 
-    // Move shared resources to Mutex
-    // free wraps a critical section see https://docs.rs/cortex-m/0.7.4/cortex_m/interrupt/fn.free.html
-    free(|cs| {
-        TIMER_TIM2.borrow(cs).replace(Some(timer));
-        IR_DIODE.borrow(cs).replace(Some(ir_diode));
-    });
+    defmt::println!("Start receiving ...");
 
-    defmt::println!("Start receiving ... (zero start, big endian)");
+    let mut stream = PulseStream::new(PAUSE_US);
+    let mut receiver = IrReceiver::new();
+    let mut lost = 0;
 
     loop {
-        // asm::wfi();  // does not work in conjunction with  rtt defmt
+        let (edge, now, lost_edges) = free(|cs| {
+            let mut edges = EDGES.borrow(cs).borrow_mut();
+            let now = CAPTURE.borrow(cs).borrow().as_ref().map_or(0, |c| c.now());
+            (edges.pop(), now, edges.lost())
+        });
+        if lost_edges != lost {
+            lost = lost_edges;
+            defmt::warn!("{} edges lost", lost);
+        }
+        let pulse = match edge {
+            Some(edge) => stream.edge(edge),
+            None => stream.timeout(now),
+        };
+        if let Some((carrier, duration)) = pulse {
+            if let Some(frame) = receiver.next(carrier, duration) {
+                defmt::println!("{}: {}", frame.protocol(), frame);
+            }
+        }
     }
 }
@@ -9,7 +9,13 @@ use board::hal::nb::block;
 use board::hal::prelude::*;
 use board::hal::stm32;
 
-use manchester_code::{BitOrder, Decoder, SyncOnTurningEdge, ActivityLevel};
+use board::infrared::capture::SampledPulses;
+use board::infrared::key::KeyTracker;
+use board::infrared::receiver::IrReceiver;
+use board::infrared::IrDecoder;
+
+/// Sample period in µs, well below the 444 µs half bit of RC6
+const PERIOD_US: u32 = 50;
 
 #[cortex_m_rt::entry]
 fn main() -> ! {
@@ -21,23 +27,25 @@ fn main() -> ! {
     let infrared = gpiob.pb3.into_floating_input();
 
     let mut timer = dp.TIM17.timer(&mut rcc);
-    timer.start(296.us()); // 889 µs / 4; aka 4 samples per half bit period
-    let mut receiver = Decoder::new(
-        ActivityLevel::Low,
-        SyncOnTurningEdge::Second,
-        BitOrder::BigEndian,
-    );
-    defmt::println!("Start receiving ... (big endian)");
+    timer.start(PERIOD_US.us());
+    let mut pulses = SampledPulses::new(PERIOD_US, 10_000);
+    let mut receiver = IrReceiver::new();
+    let mut keys = KeyTracker::new();
+    let mut ticks: u64 = 0;
+    defmt::println!("Start receiving ...");
 
     loop {
-        match receiver.next(infrared.is_high().unwrap()) {
-            None => (),
-            Some(t) => {
-                if t.len() > 2 {
-                    defmt::println!("Datagram: {:?}", t);
-                }
+        let now = (ticks * PERIOD_US as u64 / 1000) as u32; // ms
+        ticks += 1;
+        if let Some((carrier, duration)) = pulses.sample(infrared.is_high().unwrap()) {
+            if let Some(frame) = receiver.next(carrier, duration) {
+                defmt::println!("{}: {}", frame.protocol(), frame);
+                keys.ir(now, &frame);
             }
-        };
+        }
+        while let Some(event) = keys.poll(now) {
+            defmt::println!("Key: {}", event);
+        }
         block!(timer.wait()).unwrap();
     }
 }
@@ -0,0 +1,99 @@
+// Send RC5 codes by DMA
+//
+// The carrier is generated on Arduino PIN D5 (PB4 / TIM3_CH1) like in
+// infrared-send.rs. The half bits are written to the compare register of
+// TIM3 by DMA channel 1 on every update of TIM6, the CPU is only
+// interrupted once the frame is sent.
+
+#![no_main]
+#![no_std]
+
+use nucleo_stm32g071rb as board;
+
+use cortex_m::{
+    self,
+    interrupt::{free, Mutex},
+};
+
+use board::hal::{
+    dma::{self, DmaExt},
+    interrupt,
+    prelude::*,
+    stm32,
+};
+use board::infrared::dma::{DmaTransmitter, BUFFER_UNITS};
+use board::infrared::rc5::{self, Rc5Frame};
+use board::infrared::receiver::IrFrame;
+use board::infrared::transmitter::PwmCarrier;
+
+use core::cell::RefCell;
+use core::ops::DerefMut;
+
+static mut BUFFER: [u16; BUFFER_UNITS] = [0; BUFFER_UNITS];
+static TRANSMITTER: Mutex<RefCell<Option<DmaTransmitter<dma::C1>>>> =
+    Mutex::new(RefCell::new(None));
+
+#[interrupt]
+fn DMA_CHANNEL1() {
+    free(|cs| {
+        if let Some(transmitter) = TRANSMITTER.borrow(cs).borrow_mut().deref_mut() {
+            transmitter.on_interrupt();
+        }
+    });
+}
+
+/// Completion callback, called by the interrupt handler
+fn sent() {
+    defmt::println!("Frame sent");
+}
+
+#[cortex_m_rt::entry]
+fn main() -> ! {
+    let dp = stm32::Peripherals::take().expect("cannot take peripherals");
+    let mut rcc = dp.RCC.constrain();
+
+    // Setup PWM we use Arduino PIN D5 -> is PB4 / TIM3_CH1 on stm32g071
+    let gpiob = dp.GPIOB.split(&mut rcc);
+    let pwm = dp.TIM3.pwm(rc5::CARRIER_HZ.hz(), &mut rcc);
+    let pwm_send_ir = pwm.bind_pin(gpiob.pb4);
+    let carrier = PwmCarrier::new(pwm, pwm_send_ir, &rcc);
+
+    let channels = dp.DMA.split(&mut rcc, dp.DMAMUX);
+    // the only reference to the buffer, it is read by the DMA
+    let buffer = unsafe { &mut *core::ptr::addr_of_mut!(BUFFER) };
+    let transmitter =
+        DmaTransmitter::tim6(dp.TIM6, carrier, channels.ch1, buffer, &mut rcc).with_callback(sent);
+
+    free(|cs| {
+        TRANSMITTER.borrow(cs).replace(Some(transmitter));
+    });
+
+    // Enable interrupt
+    stm32::NVIC::unpend(interrupt::DMA_CHANNEL1);
+    unsafe {
+        stm32::NVIC::unmask(interrupt::DMA_CHANNEL1);
+    }
+
+    defmt::println!("Init done");
+
+    // volume up (command 16) to a TV (address 0)
+    let mut frame = Rc5Frame::new(0, 16, false);
+
+    let mut delay = dp.TIM15.delay(&mut rcc);
+
+    loop {
+        delay.delay(1000.ms());
+
+        let started = free(|cs| {
+            let mut transmitter = TRANSMITTER.borrow(cs).borrow_mut();
+            transmitter
+                .as_mut()
+                .is_some_and(|t| t.send(IrFrame::Rc5(frame)).is_ok())
+        });
+        if started {
+            defmt::println!("Send new frame {}", frame);
+            // each transmission is a new key press
+            frame.toggle = !frame.toggle;
+        }
+    }
+}
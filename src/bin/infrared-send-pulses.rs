@@ -0,0 +1,113 @@
+// Send NEC and Sony SIRC codes
+//
+// The carrier is generated on Arduino PIN D5 (PB4 / TIM3_CH1) like in
+// infrared-send.rs, the transmitter switches it between 38 kHz with 33% duty
+// cycle (NEC) and 40 kHz with 25% duty cycle (SIRC). TIM2 is restarted with
+// the duration of every mark or space.
+
+#![deny(warnings)]
+#![no_main]
+#![no_std]
+
+use nucleo_stm32g071rb as board;
+
+use cortex_m::{
+    self,
+    interrupt::{free, Mutex},
+};
+
+use board::hal::{
+    interrupt,
+    prelude::*,
+    stm32::{self, TIM2},
+    timer::{Channel1, Timer},
+};
+use board::infrared::nec::{self, NecCode, NecFrame};
+use board::infrared::receiver::{IrFrame, Protocol};
+use board::infrared::sirc::{SircBits, SircFrame};
+use board::infrared::transmitter::{CarrierConfig, IrTransmitter, PwmCarrier};
+
+use core::cell::RefCell;
+use core::ops::DerefMut;
+
+type Transmitter = IrTransmitter<PwmCarrier<Channel1>, Timer<TIM2>, 4>;
+
+static TRANSMITTER: Mutex<RefCell<Option<Transmitter>>> = Mutex::new(RefCell::new(None));
+
+#[interrupt]
+fn TIM2() {
+    free(|cs| {
+        if let Some(transmitter) = TRANSMITTER.borrow(cs).borrow_mut().deref_mut() {
+            transmitter.on_interrupt();
+        }
+    });
+}
+
+/// Queue a frame, waiting while the queue is full
+fn send(frame: IrFrame, repeats: u8) -> u32 {
+    loop {
+        let queued = free(|cs| {
+            let mut transmitter = TRANSMITTER.borrow(cs).borrow_mut();
+            transmitter.as_mut().map(|t| t.send(frame, repeats))
+        });
+        if let Some(Ok(sequence)) = queued {
+            return sequence;
+        }
+    }
+}
+
+/// True once the frame is sent
+fn is_sent(sequence: u32) -> bool {
+    free(|cs| {
+        let transmitter = TRANSMITTER.borrow(cs).borrow();
+        transmitter.as_ref().map_or(true, |t| t.is_sent(sequence))
+    })
+}
+
+#[cortex_m_rt::entry]
+fn main() -> ! {
+    let dp = stm32::Peripherals::take().expect("cannot take peripherals");
+    let mut rcc = dp.RCC.constrain();
+
+    // Setup PWM we use Arduino PIN D5 -> is PB4 / TIM3_CH1 on stm32g071
+    let gpiob = dp.GPIOB.split(&mut rcc);
+    let pwm = dp.TIM3.pwm(nec::CARRIER_HZ.hz(), &mut rcc);
+    let pwm_send_ir = pwm.bind_pin(gpiob.pb4);
+
+    // Set up the bit clock, it is restarted for every mark and space
+    let mut timer = dp.TIM2.timer(&mut rcc);
+    timer.listen();
+    let carrier = PwmCarrier::new(pwm, pwm_send_ir, &rcc);
+    // 33% duty cycle for NEC
+    let nec_carrier = CarrierConfig::new(nec::CARRIER_HZ, 33);
+    defmt::println!("NEC carrier {} Hz", carrier.achieved_hz(nec_carrier));
+    let transmitter = IrTransmitter::new(carrier, timer).with_carrier(Protocol::Nec, nec_carrier);
+
+    free(|cs| {
+        TRANSMITTER.borrow(cs).replace(Some(transmitter));
+    });
+
+    // Enable interrupt
+    stm32::NVIC::unpend(interrupt::TIM2);
+    unsafe {
+        stm32::NVIC::unmask(interrupt::TIM2);
+    }
+
+    defmt::println!("Init done");
+
+    // volume up of a cheap car mp3 remote and of a Sony TV
+    let nec_frame = NecFrame::new(0x00, 0x46);
+    let sirc_frame = SircFrame::new(SircBits::Twelve, 18, 1, 0);
+    let mut delay = dp.TIM15.delay(&mut rcc);
+
+    loop {
+        // held for three repeat periods
+        defmt::println!("Send NEC frame {}", nec_frame);
+        send(IrFrame::Nec(NecCode::Frame(nec_frame)), 3);
+        // a SIRC frame is sent at least three times
+        defmt::println!("Send SIRC frame {}", sirc_frame);
+        let sequence = send(IrFrame::Sirc(sirc_frame), 2);
+        while !is_sent(sequence) {}
+        delay.delay(1000.ms());
+    }
+}
@@ -5,7 +5,9 @@
 
 use nucleo_stm32g071rb as board; //  it also includes mem, defmt
 
-use board::lcd::{Color, RgbLCD};
+use board::i2c::bus_clear;
+use board::i2c::timing::{Mode, Parameters};
+use board::lcd::{charset::Translator, recovery::RecoveringBus, Color, RgbLCD};
 
 use board::hal::i2c::Config;
 use board::hal::prelude::*;
@@ -18,38 +20,77 @@ fn main() -> ! {
     let dp = stm32::Peripherals::take().expect("cannot take peripherals");
     let mut rcc = dp.RCC.constrain();
     let delay = dp.TIM15.delay(&mut rcc);
+    let bus_delay = dp.TIM16.delay(&mut rcc);
+    let mut clear_delay = dp.TIM17.delay(&mut rcc);
 
     let gpiob = dp.GPIOB.split(&mut rcc);
 
     let sda = gpiob.pb9.into_open_drain_output();
     let scl = gpiob.pb8.into_open_drain_output();
 
-    let mut i2c = dp.I2C1.i2c(
-        sda,
-        scl,
-        // Config::with_timing(0x2020_151b),
-        100.khz(),
-        &mut rcc,
-    );
+    // fast mode, the LCD controller does not support Fast-mode Plus
+    let timing = Parameters::new(rcc.clocks.apb_clk.0, Mode::Fast)
+        .compute()
+        .expect("no valid I2C timing")
+        .bits();
+    let i2c = dp.I2C1.i2c(sda, scl, Config::with_timing(timing), &mut rcc);
+
+    // survive hot-plugging of the Grove cable
+    let mut i2c = RecoveringBus::new(i2c, bus_delay).with_bus_clear(move |i2c| {
+        let (i2c1, mut sda, mut scl) = i2c.release();
+        if !bus_clear(&mut scl, &mut sda, &mut clear_delay) {
+            defmt::warn!("SDA is held low");
+        }
+        i2c1.i2c(sda, scl, Config::with_timing(timing), &mut rcc)
+    });
 
     defmt::info!("I2C initialized");
 
     let mut lcd = RgbLCD::new();
-    lcd.init(&mut i2c, delay).unwrap();
+    if lcd.init(&mut i2c, delay).is_err() {
+        defmt::warn!("LCD not responding");
+    }
 
     defmt::info!("LCD initialized");
 
     defmt::info!("LCD switch on backlight");
-    lcd.switch_display_off(&mut i2c).unwrap();
-    lcd.set_color(&mut i2c, Color::Blue).unwrap();
-    lcd.write_byte(&mut i2c, b'R').unwrap();
+    if lcd.switch_display_on(&mut i2c).is_err()
+        || lcd.set_color(&mut i2c, Color::Blue).is_err()
+        || lcd.write_byte(&mut i2c, b'R').is_err()
+    {
+        defmt::warn!("LCD not responding");
+    }
     defmt::info!("Write R");
 
-    // loop {
-    //     match i2c.write(0x3c, &buf) {
-    //         Ok(_) => hprintln!("ok").unwrap(),
-    //         Err(err) => hprintln!("error: {:?}", err).unwrap(),
-    //     }
-    // }
-    nucleo_stm32g071rb::exit()
+    let text = "25°C µ→ä Ö\\";
+    let mut translator = Translator::new().with_glyph_slots(6, 2);
+    if translator.load_glyphs(&lcd, &mut i2c, text).is_err()
+        || lcd.set_cursor(&mut i2c, 0, 1).is_err()
+        || lcd.write_translated(&mut i2c, &translator, text).is_err()
+    {
+        defmt::warn!("LCD not responding");
+    }
+    defmt::info!("Write translated text");
+
+    // count seconds, unplugged and re-plugged display shows all of it
+    let mut seconds: u32 = 0;
+    let mut recoveries = 0;
+    loop {
+        cortex_m::asm::delay(16_000_000); // 1 s at 16 MHz HSI
+        seconds += 1;
+        let digits = [
+            b'0' + (seconds / 100 % 10) as u8,
+            b'0' + (seconds / 10 % 10) as u8,
+            b'0' + (seconds % 10) as u8,
+        ];
+        let result = lcd
+            .set_cursor(&mut i2c, 13, 0)
+            .and_then(|_| lcd.write_bytes(&mut i2c, &digits));
+        if result.is_err() {
+            defmt::warn!("LCD not responding");
+        } else if i2c.recoveries() != recoveries {
+            recoveries = i2c.recoveries();
+            defmt::info!("LCD recovered");
+        }
+    }
 }
@@ -0,0 +1,108 @@
+#![deny(warnings)]
+#![deny(unsafe_code)]
+#![no_main]
+#![no_std]
+
+use nucleo_stm32g071rb as board;
+
+use board::hal::nb::block;
+use board::hal::prelude::*;
+use board::hal::stm32;
+
+use board::lcd::{Color, RgbLCD};
+use board::ui::encoder::EncoderInput;
+use board::ui::menu::{Item, Menu, MenuEvent, Value};
+use board::ui::screen::Page;
+use board::ui::screensaver::{Screensaver, SleepMode};
+use board::ui::Frame;
+
+const MODES: [&str; 3] = ["RC5", "NEC", "SIRC"];
+
+const ID_MODE: u8 = 1;
+const ID_ADDRESS: u8 = 2;
+const ID_COLOR: u8 = 3;
+const ID_BLINK: u8 = 4;
+const ID_CLEAR: u8 = 5;
+
+#[cortex_m_rt::entry]
+fn main() -> ! {
+    let dp = stm32::Peripherals::take().expect("cannot take peripherals");
+    let mut rcc = dp.RCC.constrain();
+    let delay = dp.TIM15.delay(&mut rcc);
+
+    // Grove LCD at I2C1 (Arduino D14/D15)
+    let gpiob = dp.GPIOB.split(&mut rcc);
+    let sda = gpiob.pb9.into_open_drain_output();
+    let scl = gpiob.pb8.into_open_drain_output();
+    let mut i2c = dp.I2C1.i2c(sda, scl, 100.khz(), &mut rcc);
+
+    // Rotary encoder at Arduino A0/A1, push button at A2
+    let gpioa = dp.GPIOA.split(&mut rcc);
+    let mut encoder = EncoderInput::new(
+        gpioa.pa0.into_pull_up_input(),
+        gpioa.pa1.into_pull_up_input(),
+        gpioa.pa4.into_pull_up_input(),
+        4,   // state changes per detent
+        800, // ms for long press
+    );
+
+    let mut lcd = RgbLCD::new();
+    lcd.init(&mut i2c, delay).unwrap();
+
+    let mut infrared = [
+        Item::choice("Protocol", ID_MODE, &MODES, 0),
+        Item::integer("Address", ID_ADDRESS, 0, 0, 31, 1),
+    ];
+    let mut backlight = [
+        Item::color("Color", ID_COLOR, 0, 255, 0),
+        Item::boolean("Blink", ID_BLINK, false),
+    ];
+    let mut root = [
+        Item::submenu("Infrared", &mut infrared),
+        Item::submenu("Backlight", &mut backlight),
+        Item::action("Clear", ID_CLEAR),
+    ];
+    let mut menu = Menu::new(&mut root);
+
+    // dim after 30 s, switch off after 2 min of inactivity
+    let mut screensaver =
+        Screensaver::new(30_000, 120_000, 40, SleepMode::DisplayOff).with_fade_step(1);
+    screensaver.set_color(Color::Green);
+
+    let mut timer = dp.TIM17.timer(&mut rcc);
+    timer.start(1.ms());
+
+    let mut frame = Frame::new();
+    menu.render(&mut frame);
+    frame.flush(&lcd, &mut i2c).unwrap();
+    defmt::println!("Menu ready");
+
+    loop {
+        if let Some(event) = encoder.poll() {
+            // the input waking the display is not passed to the menu
+            let woken = screensaver.wake(&mut lcd, &mut i2c).unwrap();
+            let action = if woken { None } else { menu.handle(event) };
+            match action {
+                Some(MenuEvent::Changed(ID_COLOR)) => {
+                    if let Some(color) = menu.value(ID_COLOR).and_then(|v| v.as_color()) {
+                        screensaver.set_color(color);
+                    }
+                }
+                Some(MenuEvent::Changed(ID_BLINK)) => match menu.value(ID_BLINK) {
+                    Some(Value::Bool(true)) => lcd.switch_blink_backlight_on(&mut i2c).unwrap(),
+                    _ => lcd.switch_blink_backlight_off(&mut i2c).unwrap(),
+                },
+                Some(MenuEvent::Changed(id)) => defmt::println!("Value {} changed", id),
+                Some(MenuEvent::Action(id)) => defmt::println!("Action {}", id),
+                Some(MenuEvent::Exit) => menu.reset(),
+                None => (),
+            }
+            let shown = frame;
+            frame.clear();
+            menu.render(&mut frame);
+            frame.flush_changes(&lcd, &mut i2c, &shown).unwrap();
+        }
+        screensaver.tick(&mut lcd, &mut i2c).unwrap();
+        block!(timer.wait()).unwrap();
+    }
+}
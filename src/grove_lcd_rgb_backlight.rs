@@ -15,6 +15,14 @@ use embedded_hal as hal;
 use hal::blocking::delay::DelayUs;
 use hal::blocking::i2c::Write;
 
+pub mod charset;
+pub mod glyph;
+pub mod recovery;
+pub mod sprite;
+
+use charset::Translator;
+
+#[derive(Clone, Copy, PartialEq)]
 pub enum Color {
     White,
     Red,
@@ -23,12 +31,29 @@ pub enum Color {
     RGB(u8, u8, u8),
 }
 
+impl Color {
+    /// Components as (red, green, blue)
+    pub fn rgb(self) -> (u8, u8, u8) {
+        match self {
+            Color::White => (255, 255, 255),
+            Color::Red => (255, 0, 0),
+            Color::Green => (0, 255, 0),
+            Color::Blue => (0, 0, 255),
+            Color::RGB(red, green, blue) => (red, green, blue),
+        }
+    }
+}
+
 pub struct RgbLCD {
     display_function: u8,
     display_control: u8,
     display_mode: u8,
+    display_offset: u8,
 }
 
+/// Number of characters per line in DDRAM, i.e. columns of the virtual canvas
+pub const CANVAS_COLUMNS: u8 = 40;
+
 // const LCD_ADDRESS: u8 = 0x7c >> 1;
 // const RGB_ADDRESS: u8 = 0xc4 >> 1;
 const LCD_ADDRESS: u8 = 0x3e;
@@ -75,9 +100,10 @@ impl RgbLCD {
     ///
     pub fn new() -> Self {
         RgbLCD {
-            display_function: LCD_2LINE | LCD_5X8_DOTS,
+            display_function: LCD_8BITMODE | LCD_2LINE | LCD_5X8_DOTS,
             display_control: LCD_DISPLAY_ON,
-            display_mode: LCD_8BITMODE,
+            display_mode: 0,
+            display_offset: 0,
         }
     }
 
@@ -139,25 +165,27 @@ impl RgbLCD {
 
     /// clear display, set cursor position to zero
     pub fn clear_display<E, I2C: Write<Error = E>, D: DelayUs<u32>>(
-        &self,
+        &mut self,
         i2c: &mut I2C,
         block: D,
     ) -> Result<(), E> {
         const LCD_CLEAR_DISPLAY: u8 = 0x01;
         send_command(i2c, LCD_CLEAR_DISPLAY)?;
+        self.display_offset = 0;
         let mut block = block;
         block.delay_us(2000); // this command takes a long time!
         Ok(())
     }
 
-    /// set cursor position to zero
+    /// set cursor position to zero, undo any display scrolling
     pub fn home<E, I2C: Write<Error = E>, D: DelayUs<u32>>(
-        &self,
+        &mut self,
         i2c: &mut I2C,
         block: D,
     ) -> Result<(), E> {
         const LCD_RETURN_HOME: u8 = 0x02;
         send_command(i2c, LCD_RETURN_HOME)?;
+        self.display_offset = 0;
         let mut block = block;
         block.delay_us(2000); // this command takes a long time!
         Ok(())
@@ -210,14 +238,46 @@ impl RgbLCD {
         i2c: &mut I2C,
     ) -> Result<(), E> {
         // This commands scroll the display without changing the RAM
-        send_command(i2c, LCD_CURSOR_SHIFT | LCD_DISPLAY_MOVE | LCD_MOVE_LEFT)
+        send_command(i2c, LCD_CURSOR_SHIFT | LCD_DISPLAY_MOVE | LCD_MOVE_LEFT)?;
+        self.display_offset = (self.display_offset + 1) % CANVAS_COLUMNS;
+        Ok(())
     }
 
     pub fn scroll_display_right<E, I2C: Write<Error = E>>(
         &mut self,
         i2c: &mut I2C,
     ) -> Result<(), E> {
-        send_command(i2c, LCD_CURSOR_SHIFT | LCD_DISPLAY_MOVE | LCD_MOVE_RIGHT)
+        send_command(i2c, LCD_CURSOR_SHIFT | LCD_DISPLAY_MOVE | LCD_MOVE_RIGHT)?;
+        self.display_offset = (self.display_offset + CANVAS_COLUMNS - 1) % CANVAS_COLUMNS;
+        Ok(())
+    }
+
+    /// Canvas column shown in the leftmost display column
+    pub fn display_offset(&self) -> u8 {
+        self.display_offset
+    }
+
+    /// Scroll the display until canvas column offset is shown leftmost
+    ///
+    /// The visible window wraps around the end of the 40 column canvas.
+    pub fn scroll_display_to<E, I2C: Write<Error = E>>(
+        &mut self,
+        i2c: &mut I2C,
+        offset: u8,
+    ) -> Result<(), E> {
+        let offset = offset % CANVAS_COLUMNS;
+        let steps = (offset + CANVAS_COLUMNS - self.display_offset) % CANVAS_COLUMNS;
+        // take the shorter way
+        if steps <= CANVAS_COLUMNS / 2 {
+            for _ in 0..steps {
+                self.scroll_display_left(i2c)?;
+            }
+        } else {
+            for _ in steps..CANVAS_COLUMNS {
+                self.scroll_display_right(i2c)?;
+            }
+        }
+        Ok(())
     }
 
     /// Text that flows Left to Right
@@ -264,7 +324,7 @@ impl RgbLCD {
         location: u8,
         charmap: [u8; 8],
     ) -> Result<(), E> {
-        if location <= 8 {
+        if location > 7 {
             panic!("Location must be in range 0..7");
         }
         send_command(i2c, LCD_SET_CGRAM_ADDR | (location << 3))?;
@@ -277,12 +337,17 @@ impl RgbLCD {
     }
 
     /// Position the cursor
+    ///
+    /// col is a canvas column in range 0..39, i.e. independent of display scrolling.
+    /// Positions beyond the canvas are clamped to its last column or row.
     pub fn set_cursor<E, I2C: Write<Error = E>>(
         &self,
         i2c: &mut I2C,
         col: u8,
         row: u8,
     ) -> Result<(), E> {
+        let col = col.min(CANVAS_COLUMNS - 1);
+        let row = row.min(1);
         let pos: u8 = 0x80 + col + row * (0xc0 - 0x80);
         let data: [u8; 2] = [0x80, pos];
         i2c.write(LCD_ADDRESS, &data)?;
@@ -296,7 +361,49 @@ impl RgbLCD {
         Ok(())
     }
 
-    /// Send text
+    /// Send a sequence of character codes
+    pub fn write_bytes<E, I2C: Write<Error = E>>(
+        &self,
+        i2c: &mut I2C,
+        values: &[u8],
+    ) -> Result<(), E> {
+        // control byte followed by up to 16 data bytes per transmission
+        let mut data: [u8; 17] = [0x40; 17];
+        for chunk in values.chunks(16) {
+            data[1..=chunk.len()].copy_from_slice(chunk);
+            i2c.write(LCD_ADDRESS, &data[..=chunk.len()])?;
+        }
+        Ok(())
+    }
+
+    /// Send text, characters missing in the character ROM are replaced by `?`
+    pub fn write_str<E, I2C: Write<Error = E>>(&self, i2c: &mut I2C, text: &str) -> Result<(), E> {
+        self.write_translated(i2c, &Translator::new(), text)
+    }
+
+    /// Send text, characters are mapped to character codes by the translator
+    pub fn write_translated<E, I2C: Write<Error = E>>(
+        &self,
+        i2c: &mut I2C,
+        translator: &Translator,
+        text: &str,
+    ) -> Result<(), E> {
+        // control byte followed by up to 16 data bytes per transmission
+        let mut data: [u8; 17] = [0x40; 17];
+        let mut len = 1;
+        for c in text.chars() {
+            data[len] = translator.translate(c);
+            len += 1;
+            if len == data.len() {
+                i2c.write(LCD_ADDRESS, &data)?;
+                len = 1;
+            }
+        }
+        if len > 1 {
+            i2c.write(LCD_ADDRESS, &data[..len])?;
+        }
+        Ok(())
+    }
 
     pub fn switch_blink_backlight_on<E, I2C: Write<Error = E>>(
         &self,
@@ -320,13 +427,7 @@ impl RgbLCD {
         i2c: &mut I2C,
         color: Color,
     ) -> Result<(), E> {
-        let (red, green, blue) = match color {
-            Color::White => (255, 255, 255),
-            Color::Red => (255, 0, 0),
-            Color::Green => (0, 255, 0),
-            Color::Blue => (0, 0, 255),
-            Color::RGB(red, green, blue) => (red, green, blue),
-        };
+        let (red, green, blue) = color.rgb();
         const REG_RED: u8 = 0x04; // pwm2
         const REG_GREEN: u8 = 0x03; // pwm1
         const REG_BLUE: u8 = 0x02; // pwm0
@@ -337,6 +438,12 @@ impl RgbLCD {
     }
 }
 
+impl Default for RgbLCD {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 fn set_register<E, I2C: Write<Error = E>>(i2c: &mut I2C, address: u8, value: u8) -> Result<(), E> {
     let data: [u8; 2] = [address, value];
     i2c.write(RGB_ADDRESS, &data)?; // blocking transmission
@@ -348,3 +455,39 @@ fn send_command<E, I2C: Write<Error = E>>(i2c: &mut I2C, command: u8) -> Result<
     i2c.write(LCD_ADDRESS, &data)?;
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::I2cMock;
+
+    #[test]
+    fn pan_visible_window_over_canvas() {
+        let mut lcd = RgbLCD::new();
+        let mut i2c = I2cMock::new();
+        lcd.set_cursor(&mut i2c, 0, 0).unwrap();
+        lcd.write_str(&mut i2c, "0123456789abcdefghijklmnopqrstuvwxyzABCD")
+            .unwrap();
+        lcd.set_cursor(&mut i2c, 36, 1).unwrap();
+        lcd.write_str(&mut i2c, "end").unwrap();
+
+        lcd.scroll_display_to(&mut i2c, 24).unwrap();
+        assert_eq!(lcd.display_offset(), 24);
+        assert_eq!(i2c.line(0), "opqrstuvwxyzABCD");
+        assert_eq!(&i2c.line(1)[12..15], "end");
+
+        // wrap around the canvas end the shorter way
+        let writes = i2c.writes.len();
+        lcd.scroll_display_to(&mut i2c, 2).unwrap();
+        assert_eq!(i2c.writes.len(), writes + 18);
+        assert_eq!(i2c.line(0), "23456789abcdefgh");
+    }
+
+    #[test]
+    fn cursor_is_clamped_to_the_canvas() {
+        let lcd = RgbLCD::new();
+        let mut i2c = I2cMock::new();
+        lcd.set_cursor(&mut i2c, 45, 3).unwrap();
+        assert_eq!(i2c.cursor(), (39, 1));
+    }
+}
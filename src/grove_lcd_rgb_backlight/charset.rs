@@ -0,0 +1,254 @@
+//!
+//! Translation of UTF-8 text into character codes of the AIP31068L
+//!
+//! The controller (JHD1313) ships with the A00 character ROM. Besides ASCII it
+//! contains some greek letters, umlauts and arrows at codes above 0x7f.
+//! Note: The ROM has a yen sign at 0x5c and arrows at 0x7e/0x7f, hence
+//! backslash and tilde cannot be displayed without a custom glyph.
+//!
+//! Characters not available in the ROM are replaced by a fallback character or,
+//! if glyph slots are configured, rendered as custom character in CGRAM.
+
+use embedded_hal as hal;
+
+use hal::blocking::i2c::Write;
+
+use super::RgbLCD;
+use crate::glyph;
+
+/// Non ASCII characters available in the A00 character ROM
+const ROM_CODES: [(char, u8); 27] = [
+    ('¥', 0x5c),
+    ('→', 0x7e),
+    ('←', 0x7f),
+    ('·', 0xa5),
+    ('°', 0xdf),
+    ('α', 0xe0),
+    ('ä', 0xe1),
+    ('β', 0xe2),
+    ('ß', 0xe2), // looks like a beta
+    ('ε', 0xe3),
+    ('µ', 0xe4), // micro sign
+    ('μ', 0xe4), // greek mu
+    ('σ', 0xe5),
+    ('ρ', 0xe6),
+    ('√', 0xe8),
+    ('¢', 0xec),
+    ('ñ', 0xee),
+    ('ö', 0xef),
+    ('θ', 0xf2),
+    ('∞', 0xf3),
+    ('Ω', 0xf4),
+    ('ü', 0xf5),
+    ('Σ', 0xf6),
+    ('π', 0xf7),
+    ('÷', 0xfd),
+    ('█', 0xff),
+    ('\u{00a0}', b' '), // no-break space
+];
+
+/// Glyphs (5x8 dots) for some characters missing in the character ROM
+const MISSING_GLYPHS: [(char, [u8; 8]); 7] = [
+    (
+        '\\',
+        glyph!(".....", "#....", ".#...", "..#..", "...#.", "....#", ".....", "....."),
+    ),
+    (
+        '~',
+        glyph!(".....", ".....", ".#...", "#.#.#", "...#.", ".....", ".....", "....."),
+    ),
+    (
+        'Ä',
+        glyph!(".#.#.", ".....", ".###.", "#...#", "#####", "#...#", "#...#", "....."),
+    ),
+    (
+        'Ö',
+        glyph!(".#.#.", ".....", ".###.", "#...#", "#...#", "#...#", ".###.", "....."),
+    ),
+    (
+        'Ü',
+        glyph!(".#.#.", ".....", "#...#", "#...#", "#...#", "#...#", ".###.", "....."),
+    ),
+    (
+        '€',
+        glyph!("..##.", ".#..#", "###..", ".#...", "###..", ".#..#", "..##.", "....."),
+    ),
+    (
+        '…',
+        glyph!(".....", ".....", ".....", ".....", ".....", ".....", "#.#.#", "....."),
+    ),
+];
+
+/// Get the character ROM code of a character
+///
+/// Returns
+/// * the ROM code or None if the character is not available in the ROM
+pub fn rom_code(c: char) -> Option<u8> {
+    match c {
+        '\\' | '~' => None, // overlaid by yen and right arrow
+        ' '..='}' => Some(c as u8),
+        _ => ROM_CODES
+            .iter()
+            .find(|(character, _)| *character == c)
+            .map(|(_, code)| *code),
+    }
+}
+
+/// Get the built-in custom glyph of a character missing in the character ROM
+pub fn missing_glyph(c: char) -> Option<[u8; 8]> {
+    MISSING_GLYPHS
+        .iter()
+        .find(|(character, _)| *character == c)
+        .map(|(_, glyph)| *glyph)
+}
+
+/// Translates unicode characters into character codes
+///
+/// By default unsupported characters are replaced by `?` and no CGRAM slots are used.
+pub struct Translator {
+    fallback: u8,
+    first_slot: u8,
+    slot_count: u8,
+    slots: [Option<char>; 8],
+}
+
+impl Translator {
+    pub const fn new() -> Self {
+        Translator {
+            fallback: b'?',
+            first_slot: 0,
+            slot_count: 0,
+            slots: [None; 8],
+        }
+    }
+
+    /// Replace unsupported characters by fallback
+    ///
+    /// The fallback must be available in the character ROM.
+    pub fn with_fallback(mut self, fallback: char) -> Self {
+        self.fallback = match rom_code(fallback) {
+            Some(code) => code,
+            None => panic!("Fallback must be available in the character ROM"),
+        };
+        self
+    }
+
+    /// Allow to generate glyphs for missing characters in CGRAM slots
+    /// first_slot .. first_slot + count
+    pub fn with_glyph_slots(mut self, first_slot: u8, count: u8) -> Self {
+        if first_slot as u16 + count as u16 > 8 {
+            panic!("Glyph slots must be in range 0..7");
+        }
+        self.first_slot = first_slot;
+        self.slot_count = count;
+        self.slots = [None; 8];
+        self
+    }
+
+    /// Get the character code to write to DDRAM
+    pub fn translate(&self, c: char) -> u8 {
+        if let Some(code) = rom_code(c) {
+            return code;
+        }
+        match self.slot_of(c) {
+            Some(slot) => slot,
+            None => self.fallback,
+        }
+    }
+
+    /// Upload glyphs for all characters of text that are missing in the ROM
+    ///
+    /// Writing CGRAM moves the address counter away from DDRAM; call it before
+    /// positioning the cursor. Characters without free slot are rendered as fallback.
+    pub fn load_glyphs<E, I2C: Write<Error = E>>(
+        &mut self,
+        lcd: &RgbLCD,
+        i2c: &mut I2C,
+        text: &str,
+    ) -> Result<(), E> {
+        for c in text.chars() {
+            if rom_code(c).is_some() || self.slot_of(c).is_some() {
+                continue;
+            }
+            let glyph = match missing_glyph(c) {
+                Some(glyph) => glyph,
+                None => continue,
+            };
+            let free = (self.first_slot..self.first_slot + self.slot_count)
+                .find(|slot| self.slots[*slot as usize].is_none());
+            if let Some(slot) = free {
+                lcd.create_custom_characters(i2c, slot, glyph)?;
+                self.slots[slot as usize] = Some(c);
+            }
+        }
+        Ok(())
+    }
+
+    /// Forget about all generated glyphs, i.e. free the glyph slots
+    pub fn release_glyphs(&mut self) {
+        self.slots = [None; 8];
+    }
+
+    fn slot_of(&self, c: char) -> Option<u8> {
+        (self.first_slot..self.first_slot + self.slot_count)
+            .find(|slot| self.slots[*slot as usize] == Some(c))
+    }
+}
+
+impl Default for Translator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::I2cMock;
+
+    #[test]
+    fn rom_codes_and_fallback() {
+        let translator = Translator::new();
+        assert_eq!(translator.translate('A'), b'A');
+        assert_eq!(translator.translate('°'), 0xdf);
+        assert_eq!(translator.translate('µ'), translator.translate('μ'));
+        // overlaid by the yen sign
+        assert_eq!(translator.translate('\\'), b'?');
+        assert_eq!(translator.translate('Ö'), b'?');
+        let translator = Translator::new().with_fallback('·');
+        assert_eq!(translator.translate('€'), 0xa5);
+    }
+
+    #[test]
+    #[should_panic(expected = "character ROM")]
+    fn fallback_must_be_in_rom() {
+        Translator::new().with_fallback('Ö');
+    }
+
+    #[test]
+    #[should_panic(expected = "Glyph slots")]
+    fn glyph_slots_out_of_range() {
+        Translator::new().with_glyph_slots(250, 10);
+    }
+
+    #[test]
+    fn glyphs_in_free_slots() {
+        let lcd = RgbLCD::new();
+        let mut i2c = I2cMock::new();
+        let mut translator = Translator::new().with_glyph_slots(6, 2);
+        translator.load_glyphs(&lcd, &mut i2c, "Ö€ÖÜä").unwrap();
+        assert_eq!(translator.translate('Ö'), 6);
+        assert_eq!(translator.translate('€'), 7);
+        // no free slot left
+        assert_eq!(translator.translate('Ü'), b'?');
+        // in the ROM, no slot used
+        assert_eq!(translator.translate('ä'), 0xe1);
+        assert_eq!(i2c.cgram[6 * 8..7 * 8], missing_glyph('Ö').unwrap());
+        assert_eq!(i2c.cgram[7 * 8..8 * 8], missing_glyph('€').unwrap());
+
+        translator.release_glyphs();
+        assert_eq!(translator.translate('Ö'), b'?');
+        translator.load_glyphs(&lcd, &mut i2c, "Ü").unwrap();
+        assert_eq!(translator.translate('Ü'), 6);
+    }
+}
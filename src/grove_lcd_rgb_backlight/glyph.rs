@@ -0,0 +1,160 @@
+//!
+//! Compile time definition of custom characters from ASCII art
+//!
+//! Each row is a string of five dots: `#` is a set pixel, `.` a cleared one.
+//! 8 rows give a 5x8 glyph, 10 rows a 5x10 glyph (the cursor line is not part
+//! of the pattern).
+//!
+//! ```
+//! use nucleo_stm32g071rb::glyph;
+//!
+//! const BELL: [u8; 8] = glyph!(
+//!     "..#..",
+//!     ".###.",
+//!     ".###.",
+//!     ".###.",
+//!     "#####",
+//!     ".....",
+//!     "..#..",
+//!     ".....",
+//! );
+//! assert_eq!(BELL[0], 0b00100);
+//! ```
+//!
+//! Wrong widths or heights or other characters than `#` and `.` do not compile:
+//!
+//! ```compile_fail
+//! use nucleo_stm32g071rb::glyph;
+//!
+//! const TOO_WIDE: [u8; 8] = glyph!(
+//!     "..#...", ".....", ".....", ".....", ".....", ".....", ".....", ".....",
+//! );
+//! ```
+//!
+//! ```compile_fail
+//! use nucleo_stm32g071rb::glyph;
+//!
+//! const TOO_LOW: [u8; 8] = glyph!(".....", ".....", ".....");
+//! ```
+
+/// Width of a glyph in dots
+pub const GLYPH_WIDTH: usize = 5;
+
+/// Build a 5x8 glyph, i.e. the charmap of `create_custom_characters`
+pub const fn glyph_5x8(rows: [&str; 8]) -> [u8; 8] {
+    let mut glyph = [0; 8];
+    let mut i = 0;
+    while i < rows.len() {
+        glyph[i] = row_bits(rows[i]);
+        i += 1;
+    }
+    glyph
+}
+
+/// Build a 5x10 glyph
+pub const fn glyph_5x10(rows: [&str; 10]) -> [u8; 10] {
+    let mut glyph = [0; 10];
+    let mut i = 0;
+    while i < rows.len() {
+        glyph[i] = row_bits(rows[i]);
+        i += 1;
+    }
+    glyph
+}
+
+const fn row_bits(row: &str) -> u8 {
+    let row = row.as_bytes();
+    if row.len() != GLYPH_WIDTH {
+        panic!("Glyph rows must be 5 dots wide");
+    }
+    let mut bits = 0;
+    let mut i = 0;
+    while i < row.len() {
+        bits <<= 1;
+        match row[i] {
+            b'#' => bits |= 1,
+            b'.' => (),
+            _ => panic!("Glyph rows must consist of '#' and '.'"),
+        }
+        i += 1;
+    }
+    bits
+}
+
+/// Define a 5x8 or 5x10 glyph from ASCII art, evaluated at compile time
+#[macro_export]
+macro_rules! glyph {
+    ($r0:literal, $r1:literal, $r2:literal, $r3:literal, $r4:literal,
+     $r5:literal, $r6:literal, $r7:literal $(,)?) => {{
+        const GLYPH: [u8; 8] = $crate::grove_lcd_rgb_backlight::glyph::glyph_5x8([
+            $r0, $r1, $r2, $r3, $r4, $r5, $r6, $r7,
+        ]);
+        GLYPH
+    }};
+    ($r0:literal, $r1:literal, $r2:literal, $r3:literal, $r4:literal,
+     $r5:literal, $r6:literal, $r7:literal, $r8:literal, $r9:literal $(,)?) => {{
+        const GLYPH: [u8; 10] = $crate::grove_lcd_rgb_backlight::glyph::glyph_5x10([
+            $r0, $r1, $r2, $r3, $r4, $r5, $r6, $r7, $r8, $r9,
+        ]);
+        GLYPH
+    }};
+    ($($row:literal),* $(,)?) => {
+        compile_error!("A glyph has 8 (5x8) or 10 (5x10) rows")
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn glyph_5x8_rows_are_msb_left() {
+        const ARROW: [u8; 8] =
+            glyph!("..#..", ".###.", "#.#.#", "..#..", "..#..", "..#..", "..#..", ".....",);
+        assert_eq!(
+            ARROW,
+            [0b00100, 0b01110, 0b10101, 0b00100, 0b00100, 0b00100, 0b00100, 0b00000]
+        );
+    }
+
+    #[test]
+    fn glyph_5x10() {
+        const BAR: [u8; 10] = glyph!(
+            "#....", ".#...", "..#..", "...#.", "....#", "#####", ".....", ".....", "#...#",
+            ".....",
+        );
+        assert_eq!(
+            BAR,
+            [0x10, 0x08, 0x04, 0x02, 0x01, 0x1f, 0x00, 0x00, 0x11, 0x00]
+        );
+    }
+
+    #[test]
+    fn const_fn_equals_macro() {
+        let rows = [
+            "#####", "#...#", "#...#", "#...#", "#...#", "#...#", "#####", ".....",
+        ];
+        assert_eq!(
+            glyph_5x8(rows),
+            glyph!("#####", "#...#", "#...#", "#...#", "#...#", "#...#", "#####", ".....")
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "5 dots wide")]
+    fn too_narrow_row_is_rejected() {
+        let rows = [
+            "####", ".....", ".....", ".....", ".....", ".....", ".....", ".....",
+        ];
+        glyph_5x8(rows);
+    }
+
+    #[test]
+    #[should_panic(expected = "'#' and '.'")]
+    fn unknown_dot_is_rejected() {
+        let rows = [
+            "##x##", ".....", ".....", ".....", ".....", ".....", ".....", ".....",
+        ];
+        glyph_5x8(rows);
+    }
+}
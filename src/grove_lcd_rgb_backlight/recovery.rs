@@ -0,0 +1,421 @@
+//!
+//! Recovery from bus errors and power loss of the display
+//!
+//! `RecoveringBus` wraps the I2C bus the `RgbLCD` methods are called with.
+//! Failed transmissions are retried with an increasing delay. It keeps a
+//! shadow copy of the display state (character and glyph memory, display
+//! control, entry mode, shift and backlight registers). If a transmission
+//! fails for good, e.g. because the Grove cable was unplugged, the bus is
+//! cleared and the display is considered lost. As soon as it responds again,
+//! `init` is re-run and the shadowed state is replayed, so the display shows
+//! what it would have shown without interruption. Transmissions failing
+//! while the display is lost are part of the replayed state.
+//!
+//! ```ignore
+//! let mut i2c = RecoveringBus::new(i2c, delay).with_bus_clear(|i2c| {
+//!     let (i2c1, mut sda, mut scl) = i2c.release();
+//!     bus_clear(&mut scl, &mut sda, &mut clear_delay);
+//!     i2c1.i2c(sda, scl, 100.khz(), &mut rcc)
+//! });
+//! lcd.init(&mut i2c, lcd_delay)?;
+//! ```
+
+use super::hal::blocking::delay::DelayUs;
+use super::hal::blocking::i2c::Write;
+use super::*;
+use crate::i2c::TransientError;
+
+const ROW_LENGTH: usize = CANVAS_COLUMNS as usize;
+
+// backlight controller registers
+const REG_MODE1: u8 = 0x00;
+const REGISTER_COUNT: usize = 16;
+
+/// Number of attempts per transmission and delay before the first retry
+///
+/// The delay doubles with every retry.
+#[derive(Clone, Copy)]
+pub struct RetryPolicy {
+    pub attempts: u8,
+    pub backoff_us: u32,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            attempts: 3,
+            backoff_us: 100,
+        }
+    }
+}
+
+/// State of the display as set by the transmissions
+struct Shadow {
+    ddram: [u8; 2 * ROW_LENGTH],
+    cgram: [u8; 64],
+    address: u8,
+    cgram_selected: bool,
+    display_control: u8,
+    display_mode: u8,
+    shift: u8,
+    registers: [u8; REGISTER_COUNT],
+    written: u16,
+}
+
+impl Shadow {
+    fn new() -> Self {
+        Shadow {
+            ddram: [b' '; 2 * ROW_LENGTH],
+            cgram: [0; 64],
+            address: 0,
+            cgram_selected: false,
+            display_control: 0,
+            display_mode: LCD_ENTRY_LEFT,
+            shift: 0,
+            registers: [0; REGISTER_COUNT],
+            written: 0,
+        }
+    }
+
+    /// Apply a transmission, returns false if not addressed to the display
+    fn update(&mut self, address: u8, bytes: &[u8]) -> bool {
+        match (address, bytes) {
+            (LCD_ADDRESS, [0x80, command]) => self.command(*command),
+            (LCD_ADDRESS, [0x40, data @ ..]) => data.iter().for_each(|value| self.data(*value)),
+            (LCD_ADDRESS, _) => (),
+            (RGB_ADDRESS, [register, value]) => {
+                let register = (register & 0x0f) as usize;
+                self.registers[register] = *value;
+                self.written |= 1 << register;
+            }
+            _ => return false,
+        }
+        true
+    }
+
+    fn command(&mut self, command: u8) {
+        if command & LCD_SET_DDRAM_ADDR != 0 {
+            self.address = command & 0x7f;
+            self.cgram_selected = false;
+        } else if command & LCD_SET_CGRAM_ADDR != 0 {
+            self.address = command & 0x3f;
+            self.cgram_selected = true;
+        } else if command & LCD_FUNCTION_SET != 0 {
+            // replayed by init
+        } else if command & LCD_CURSOR_SHIFT != 0 {
+            let right = command & LCD_MOVE_RIGHT != 0;
+            if command & LCD_DISPLAY_MOVE != 0 {
+                self.shift_display(!right);
+            } else {
+                self.step(right);
+            }
+        } else if command & LCD_DISPLAY_CONTROL != 0 {
+            self.display_control = command & 0x07;
+        } else if command & LCD_ENTRY_MODESET != 0 {
+            self.display_mode = command & 0x03;
+        } else if command & 0x02 != 0 {
+            // return home
+            self.address = 0;
+            self.cgram_selected = false;
+            self.shift = 0;
+        } else if command & 0x01 != 0 {
+            // clear display
+            self.ddram = [b' '; 2 * ROW_LENGTH];
+            self.address = 0;
+            self.cgram_selected = false;
+            self.shift = 0;
+            self.display_mode |= LCD_ENTRY_LEFT;
+        }
+    }
+
+    fn data(&mut self, value: u8) {
+        let increment = self.display_mode & LCD_ENTRY_LEFT != 0;
+        if self.cgram_selected {
+            self.cgram[(self.address & 0x3f) as usize] = value;
+        } else {
+            let row = (self.address >= 0x40) as usize;
+            let col = ((self.address & 0x3f) as usize).min(ROW_LENGTH - 1);
+            self.ddram[row * ROW_LENGTH + col] = value;
+            if self.display_mode & LCD_ENTRY_SHIFT_INCREMENT != 0 {
+                self.shift_display(increment);
+            }
+        }
+        self.step(increment);
+    }
+
+    /// Move the address counter
+    fn step(&mut self, increment: bool) {
+        if self.cgram_selected {
+            let next = if increment { 1 } else { 0x3f };
+            self.address = (self.address + next) & 0x3f;
+            return;
+        }
+        self.address = match (increment, self.address) {
+            (true, 0x27) => 0x40,
+            (true, 0x67) => 0x00,
+            (true, address) => address + 1,
+            (false, 0x00) => 0x67,
+            (false, 0x40) => 0x27,
+            (false, address) => address - 1,
+        };
+    }
+
+    /// Shift the display, to the left is positive as with `RgbLCD::display_offset`
+    fn shift_display(&mut self, left: bool) {
+        let step = if left { 1 } else { CANVAS_COLUMNS - 1 };
+        self.shift = (self.shift + step) % CANVAS_COLUMNS;
+    }
+}
+
+/// I2C bus with retries, bus clear and replay of the display state
+pub struct RecoveringBus<I2C, D, C = fn(I2C) -> I2C> {
+    // only None while the bus is handed to the bus clear
+    i2c: Option<I2C>,
+    delay: D,
+    bus_clear: C,
+    policy: RetryPolicy,
+    shadow: Shadow,
+    lost: bool,
+    recoveries: u32,
+}
+
+impl<I2C, D: DelayUs<u32>> RecoveringBus<I2C, D> {
+    /// Wrap an initialized bus
+    ///
+    /// Args:
+    /// * i2c - the bus the display is connected to
+    /// * delay - used for retry backoff and re-initialization
+    pub fn new(i2c: I2C, delay: D) -> Self {
+        RecoveringBus {
+            i2c: Some(i2c),
+            delay,
+            bus_clear: core::convert::identity,
+            policy: RetryPolicy::default(),
+            shadow: Shadow::new(),
+            lost: false,
+            recoveries: 0,
+        }
+    }
+}
+
+impl<I2C, D: DelayUs<u32>, C: FnMut(I2C) -> I2C> RecoveringBus<I2C, D, C> {
+    /// Clear the bus after a failed transmission
+    ///
+    /// The closure releases the I2C peripheral, runs `crate::i2c::bus_clear`
+    /// on its pins and returns the re-initialized peripheral.
+    pub fn with_bus_clear<B: FnMut(I2C) -> I2C>(self, bus_clear: B) -> RecoveringBus<I2C, D, B> {
+        RecoveringBus {
+            i2c: self.i2c,
+            delay: self.delay,
+            bus_clear,
+            policy: self.policy,
+            shadow: self.shadow,
+            lost: self.lost,
+            recoveries: self.recoveries,
+        }
+    }
+
+    pub fn with_policy(mut self, policy: RetryPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    /// True if the display did not respond since the last failed transmission
+    pub fn is_lost(&self) -> bool {
+        self.lost
+    }
+
+    /// Number of times the display state was replayed
+    pub fn recoveries(&self) -> u32 {
+        self.recoveries
+    }
+
+    /// Release the bus and the delay
+    pub fn free(self) -> (I2C, D) {
+        (self.i2c.expect("bus is present"), self.delay)
+    }
+
+    fn bus(&mut self) -> &mut I2C {
+        self.i2c.as_mut().expect("bus is present")
+    }
+}
+
+impl<E, I2C, D, C> RecoveringBus<I2C, D, C>
+where
+    E: TransientError,
+    I2C: Write<Error = E>,
+    D: DelayUs<u32>,
+    C: FnMut(I2C) -> I2C,
+{
+    /// Transmit with retries on transient errors
+    fn transmit(&mut self, address: u8, bytes: &[u8]) -> Result<(), E> {
+        let mut backoff = self.policy.backoff_us;
+        let mut attempt = 1;
+        loop {
+            match self.bus().write(address, bytes) {
+                Ok(()) => return Ok(()),
+                Err(error) if error.is_transient() && attempt < self.policy.attempts => {
+                    self.delay.delay_us(backoff);
+                    backoff = backoff.saturating_mul(2);
+                    attempt += 1;
+                }
+                Err(error) => return Err(error),
+            }
+        }
+    }
+
+    /// Re-initialize the display and restore the shadowed state
+    fn replay(&mut self) -> Result<(), E> {
+        let i2c = self.i2c.as_mut().expect("bus is present");
+        let shadow = &self.shadow;
+        // a harmless command tells whether the display is back
+        send_command(i2c, LCD_SET_DDRAM_ADDR)?;
+
+        let mut lcd = RgbLCD::new();
+        lcd.init(i2c, DelayRef(&mut self.delay))?;
+        send_command(i2c, LCD_SET_CGRAM_ADDR)?;
+        lcd.write_bytes(i2c, &shadow.cgram)?;
+        for (row, line) in shadow.ddram.chunks(ROW_LENGTH).enumerate() {
+            send_command(i2c, LCD_SET_DDRAM_ADDR | (row as u8 * 0x40))?;
+            lcd.write_bytes(i2c, line)?;
+        }
+        send_command(i2c, LCD_ENTRY_MODESET | shadow.display_mode)?;
+        // shift the shorter way
+        let (count, direction) = if shadow.shift <= CANVAS_COLUMNS / 2 {
+            (shadow.shift, LCD_MOVE_LEFT)
+        } else {
+            (CANVAS_COLUMNS - shadow.shift, LCD_MOVE_RIGHT)
+        };
+        for _ in 0..count {
+            send_command(i2c, LCD_CURSOR_SHIFT | LCD_DISPLAY_MOVE | direction)?;
+        }
+        send_command(
+            i2c,
+            match shadow.cgram_selected {
+                true => LCD_SET_CGRAM_ADDR | shadow.address,
+                false => LCD_SET_DDRAM_ADDR | shadow.address,
+            },
+        )?;
+        send_command(i2c, LCD_DISPLAY_CONTROL | shadow.display_control)?;
+
+        // mode 1 first, it wakes up the oscillator
+        for register in 0..REGISTER_COUNT as u8 {
+            if shadow.written & (1 << register) != 0 {
+                set_register(i2c, register, shadow.registers[register as usize])?;
+            }
+        }
+        if shadow.written & (1 << REG_MODE1) == 0 {
+            set_register(i2c, REG_MODE1, 0)?;
+        }
+        Ok(())
+    }
+}
+
+impl<E, I2C, D, C> Write for RecoveringBus<I2C, D, C>
+where
+    E: TransientError,
+    I2C: Write<Error = E>,
+    D: DelayUs<u32>,
+    C: FnMut(I2C) -> I2C,
+{
+    type Error = E;
+
+    fn write(&mut self, address: u8, bytes: &[u8]) -> Result<(), E> {
+        let shadowed = self.shadow.update(address, bytes);
+        if shadowed && self.lost {
+            // the replay includes this transmission
+            self.replay()?;
+            self.lost = false;
+            self.recoveries += 1;
+            return Ok(());
+        }
+        self.transmit(address, bytes).inspect_err(|_| {
+            // other devices are not recovered, e.g. a NACK of an absent one
+            if shadowed {
+                self.lost = true;
+                let i2c = self.i2c.take().expect("bus is present");
+                self.i2c = Some((self.bus_clear)(i2c));
+            }
+        })
+    }
+}
+
+/// Lend a delay to a function taking it by value
+struct DelayRef<'d, D>(&'d mut D);
+
+impl<'d, D: DelayUs<u32>> DelayUs<u32> for DelayRef<'d, D> {
+    fn delay_us(&mut self, us: u32) {
+        self.0.delay_us(us)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::I2cMock;
+
+    struct NoDelay;
+
+    impl DelayUs<u32> for NoDelay {
+        fn delay_us(&mut self, _us: u32) {}
+    }
+
+    #[test]
+    fn retry_transient_errors() {
+        let mut i2c = I2cMock::new();
+        i2c.nacks = 2;
+        let mut bus = RecoveringBus::new(i2c, NoDelay);
+        let lcd = RgbLCD::new();
+        lcd.set_color(&mut bus, Color::Blue).unwrap();
+        assert!(!bus.is_lost());
+        let (i2c, _) = bus.free();
+        assert_eq!(i2c.color(), (0, 0, 255));
+    }
+
+    #[test]
+    fn replay_after_power_loss() {
+        let mut clears = 0;
+        let mut bus = RecoveringBus::new(I2cMock::new(), NoDelay).with_bus_clear(|i2c| {
+            clears += 1;
+            i2c
+        });
+        let mut lcd = RgbLCD::new();
+        lcd.init(&mut bus, NoDelay).unwrap();
+        lcd.set_color(&mut bus, Color::Blue).unwrap();
+        lcd.set_cursor(&mut bus, 18, 0).unwrap();
+        lcd.write_str(&mut bus, "hello").unwrap();
+        lcd.scroll_display_to(&mut bus, 18).unwrap();
+
+        // unplugged
+        bus.bus().power_cycle();
+        bus.bus().nacks = 4;
+        assert!(lcd.write_str(&mut bus, " world").is_err());
+        assert!(bus.is_lost());
+        assert!(lcd.write_str(&mut bus, "!").is_err());
+
+        // plugged in again
+        lcd.set_color(&mut bus, Color::Green).unwrap();
+        assert!(!bus.is_lost());
+        assert_eq!(bus.recoveries(), 1);
+        let (i2c, _) = bus.free();
+        assert_eq!(clears, 1);
+        assert_eq!(i2c.line(0), "hello world!    ");
+        assert_eq!(i2c.color(), (0, 255, 0));
+        assert_eq!(i2c.display_control & 0x04, 0x04);
+        assert_eq!(i2c.cursor(), (30, 0));
+    }
+
+    #[test]
+    fn other_devices_are_not_recovered() {
+        let mut clears = 0;
+        let mut bus = RecoveringBus::new(I2cMock::new(), NoDelay).with_bus_clear(|i2c| {
+            clears += 1;
+            i2c
+        });
+        // no device at that address
+        assert!(bus.write(0x50, &[0x00]).is_err());
+        assert!(!bus.is_lost());
+        drop(bus);
+        assert_eq!(clears, 0);
+    }
+}
@@ -0,0 +1,192 @@
+//!
+//! Animated custom characters
+//!
+//! A sprite cycles a CGRAM slot through a sequence of 5x8 frames. Only the
+//! slot bitmap is rewritten, i.e. every instance of the character code on the
+//! display animates at once without touching DDRAM.
+//!
+//! Note: Writing CGRAM moves the address counter of the LCD away from DDRAM.
+//! Position the cursor (`set_cursor`) before writing text after a frame change.
+
+use embedded_hal as hal;
+
+use hal::blocking::i2c::Write;
+
+use super::RgbLCD;
+use crate::glyph;
+
+pub const SPINNER: [[u8; 8]; 4] = [
+    glyph!(".....", "..#..", "..#..", "..#..", "..#..", "..#..", ".....", "....."),
+    glyph!(".....", "....#", "...#.", "..#..", ".#...", "#....", ".....", "....."),
+    glyph!(".....", ".....", ".....", "#####", ".....", ".....", ".....", "....."),
+    glyph!(".....", "#....", ".#...", "..#..", "...#.", "....#", ".....", "....."),
+];
+
+pub const HOURGLASS: [[u8; 8]; 3] = [
+    glyph!("#####", "#####", ".###.", "..#..", ".#.#.", "#...#", "#####", "....."),
+    glyph!("#####", "#...#", ".###.", "..#..", ".#.#.", "#.#.#", "#####", "....."),
+    glyph!("#####", "#...#", ".#.#.", "..#..", ".###.", "#####", "#####", "....."),
+];
+
+pub const HEART: [[u8; 8]; 2] = [
+    glyph!(".....", ".#.#.", "#####", "#####", "#####", ".###.", "..#..", "....."),
+    glyph!(".....", ".....", ".#.#.", ".###.", ".###.", "..#..", ".....", "....."),
+];
+
+pub const WALKER: [[u8; 8]; 2] = [
+    glyph!(".###.", ".###.", "..#..", "#####", "..#..", ".#.#.", "#...#", "....."),
+    glyph!(".###.", ".###.", "..#..", ".###.", "#.#.#", "..#..", ".#.#.", ".#.#."),
+];
+
+/// A CGRAM slot animated through a sequence of frames
+pub struct Sprite<'a> {
+    slot: u8,
+    frames: &'a [[u8; 8]],
+    ticks_per_frame: u32,
+    ticks: u32,
+    frame: usize,
+}
+
+impl<'a> Sprite<'a> {
+    /// Create a sprite
+    ///
+    /// Args:
+    /// * slot - CGRAM location in range 0..7
+    /// * frames - at least one 5x8 frame
+    /// * ticks_per_frame - number of `tick` calls a frame is shown
+    pub fn new(slot: u8, frames: &'a [[u8; 8]], ticks_per_frame: u32) -> Self {
+        if slot > 7 {
+            panic!("Slot must be in range 0..7");
+        }
+        if frames.is_empty() {
+            panic!("Sprite requires at least one frame");
+        }
+        Sprite {
+            slot,
+            frames,
+            ticks_per_frame: ticks_per_frame.max(1),
+            ticks: 0,
+            frame: 0,
+        }
+    }
+
+    /// Create a sprite that is ticked with tick_hz and shows frames_per_second
+    pub fn with_frame_rate(
+        slot: u8,
+        frames: &'a [[u8; 8]],
+        tick_hz: u32,
+        frames_per_second: u32,
+    ) -> Self {
+        Self::new(slot, frames, tick_hz / frames_per_second.max(1))
+    }
+
+    /// Character code to write to DDRAM for displaying the sprite
+    pub fn code(&self) -> u8 {
+        self.slot
+    }
+
+    /// Index of the frame currently shown
+    pub fn frame(&self) -> usize {
+        self.frame
+    }
+
+    /// Upload the current frame into the CGRAM slot
+    pub fn show<E, I2C: Write<Error = E>>(&self, lcd: &RgbLCD, i2c: &mut I2C) -> Result<(), E> {
+        lcd.create_custom_characters(i2c, self.slot, self.frames[self.frame])
+    }
+
+    /// Advance the animation, to be called from a periodic tick
+    ///
+    /// Returns
+    /// * true if the next frame was uploaded or I2C write error
+    pub fn tick<E, I2C: Write<Error = E>>(
+        &mut self,
+        lcd: &RgbLCD,
+        i2c: &mut I2C,
+    ) -> Result<bool, E> {
+        self.ticks += 1;
+        if self.ticks < self.ticks_per_frame {
+            return Ok(false);
+        }
+        self.ticks = 0;
+        self.frame = (self.frame + 1) % self.frames.len();
+        if self.frames.len() == 1 {
+            return Ok(false);
+        }
+        self.show(lcd, i2c)?;
+        Ok(true)
+    }
+
+    /// Restart with the first frame
+    pub fn rewind<E, I2C: Write<Error = E>>(
+        &mut self,
+        lcd: &RgbLCD,
+        i2c: &mut I2C,
+    ) -> Result<(), E> {
+        self.ticks = 0;
+        self.frame = 0;
+        self.show(lcd, i2c)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::I2cMock;
+
+    fn slot(i2c: &I2cMock, slot: usize) -> &[u8] {
+        &i2c.cgram[slot * 8..slot * 8 + 8]
+    }
+
+    #[test]
+    fn tick_advances_and_wraps() {
+        let lcd = RgbLCD::new();
+        let mut i2c = I2cMock::new();
+        let mut sprite = Sprite::new(3, &HOURGLASS, 2);
+        assert_eq!(sprite.code(), 3);
+        sprite.show(&lcd, &mut i2c).unwrap();
+        assert_eq!(slot(&i2c, 3), HOURGLASS[0]);
+
+        let advanced: [bool; 6] = core::array::from_fn(|_| sprite.tick(&lcd, &mut i2c).unwrap());
+        assert_eq!(advanced, [false, true, false, true, false, true]);
+        assert_eq!(sprite.frame(), 0);
+        assert_eq!(slot(&i2c, 3), HOURGLASS[0]);
+
+        sprite.tick(&lcd, &mut i2c).unwrap();
+        sprite.tick(&lcd, &mut i2c).unwrap();
+        assert_eq!(sprite.frame(), 1);
+        assert_eq!(slot(&i2c, 3), HOURGLASS[1]);
+    }
+
+    #[test]
+    fn single_frame_is_not_uploaded_again() {
+        let lcd = RgbLCD::new();
+        let mut i2c = I2cMock::new();
+        let mut sprite = Sprite::new(0, &HEART[..1], 1);
+        assert!(!sprite.tick(&lcd, &mut i2c).unwrap());
+        assert!(i2c.writes.is_empty());
+    }
+
+    #[test]
+    fn rewind_shows_the_first_frame() {
+        let lcd = RgbLCD::new();
+        let mut i2c = I2cMock::new();
+        let mut sprite = Sprite::with_frame_rate(7, &SPINNER, 100, 50);
+        for _ in 0..4 {
+            sprite.tick(&lcd, &mut i2c).unwrap();
+        }
+        assert_eq!(sprite.frame(), 2);
+        assert_eq!(slot(&i2c, 7), SPINNER[2]);
+        sprite.rewind(&lcd, &mut i2c).unwrap();
+        assert_eq!(sprite.frame(), 0);
+        assert_eq!(slot(&i2c, 7), SPINNER[0]);
+        // the tick count restarts as well
+        assert!(!sprite.tick(&lcd, &mut i2c).unwrap());
+    }
+
+    #[test]
+    #[should_panic(expected = "at least one frame")]
+    fn frames_are_required() {
+        Sprite::new(0, &[], 1);
+    }
+}
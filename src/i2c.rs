@@ -0,0 +1,66 @@
+//!
+//! I2C bus helpers
+//!
+//! Classification of bus errors for retries, the bus clear sequence to
+//! release a slave holding SDA low, the bus scan and the bus timing.
+
+use embedded_hal::blocking::delay::DelayUs;
+use embedded_hal::digital::v2::{InputPin, OutputPin};
+
+use crate::hal::i2c::Error;
+
+pub mod scan;
+pub mod timing;
+
+/// Half period of the bus clear clock, i.e. 100 kHz
+const BUS_CLEAR_HALF_PERIOD_US: u32 = 5;
+
+/// Bus errors that may disappear if the transmission is repeated
+pub trait TransientError {
+    fn is_transient(&self) -> bool;
+}
+
+/// A missing acknowledge, a lost arbitration or a misplaced start/stop is
+/// worth a retry, e.g. due to a hot-plugged device or a glitch.
+impl TransientError for Error {
+    fn is_transient(&self) -> bool {
+        matches!(self, Error::Nack | Error::ArbitrationLost | Error::BusError)
+    }
+}
+
+/// Clear the bus by clocking out a slave that holds SDA low
+///
+/// SCL is pulsed up to 9 times until SDA is released, followed by a stop
+/// condition. Both pins must be open drain outputs, i.e. the I2C peripheral
+/// must be released before.
+///
+/// Returns
+/// * true if SDA is released
+pub fn bus_clear<SCL, SDA, D>(scl: &mut SCL, sda: &mut SDA, delay: &mut D) -> bool
+where
+    SCL: OutputPin,
+    SDA: OutputPin + InputPin,
+    D: DelayUs<u32>,
+{
+    sda.set_high().ok();
+    scl.set_high().ok();
+    delay.delay_us(BUS_CLEAR_HALF_PERIOD_US);
+    for _ in 0..9 {
+        if sda.is_high().unwrap_or(false) {
+            break;
+        }
+        scl.set_low().ok();
+        delay.delay_us(BUS_CLEAR_HALF_PERIOD_US);
+        scl.set_high().ok();
+        delay.delay_us(BUS_CLEAR_HALF_PERIOD_US);
+    }
+    // stop condition: SDA rises while SCL is high
+    scl.set_low().ok();
+    sda.set_low().ok();
+    delay.delay_us(BUS_CLEAR_HALF_PERIOD_US);
+    scl.set_high().ok();
+    delay.delay_us(BUS_CLEAR_HALF_PERIOD_US);
+    sda.set_high().ok();
+    delay.delay_us(BUS_CLEAR_HALF_PERIOD_US);
+    sda.is_high().unwrap_or(false)
+}
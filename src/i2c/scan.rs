@@ -0,0 +1,176 @@
+//!
+//! Bus scan and identification of common Grove devices
+//!
+//! All 7-bit addresses except the reserved ones are probed by reading a
+//! byte. Known write-only devices, e.g. the text LCD, do not acknowledge a
+//! read; they are probed by writing a control byte without any command.
+//! Responders are looked up by address and, where the device has an
+//! identification register, by its content.
+//!
+//! ```ignore
+//! for device in scan(&mut i2c) {
+//!     defmt::println!("{=u8:#04x} {}", device.address, device.name.unwrap_or("unknown"));
+//! }
+//! ```
+
+use embedded_hal::blocking::i2c::{Read, Write, WriteRead};
+
+/// Lowest address not reserved by the I2C specification
+pub const FIRST_ADDRESS: u8 = 0x08;
+/// Highest address not reserved by the I2C specification
+pub const LAST_ADDRESS: u8 = 0x77;
+
+/// Device responding on the bus
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Device {
+    pub address: u8,
+    /// Identified device, None if unknown
+    pub name: Option<&'static str>,
+}
+
+/// Known device at an address, optionally identified by (register, mask, value)
+struct Known {
+    address: u8,
+    name: &'static str,
+    id: Option<(u8, u8, u8)>,
+}
+
+const fn known(address: u8, name: &'static str, id: Option<(u8, u8, u8)>) -> Known {
+    Known { address, name, id }
+}
+
+/// Addresses of devices that only support writes, i.e. SSD1306 and JHD1313
+const WRITE_ONLY: [u8; 2] = [0x3c, 0x3e];
+
+/// Devices with an identification register come first at an address
+const KNOWN: [Known; 17] = [
+    known(0x19, "LIS3DH accelerometer", Some((0x0f, 0xff, 0x33))),
+    known(0x23, "BH1750 light sensor", None),
+    known(0x29, "VL53L0X distance sensor", Some((0xc0, 0xff, 0xee))),
+    known(0x38, "AHT20 humidity sensor", None),
+    known(0x3c, "SSD1306 OLED display", None),
+    known(0x3e, "JHD1313 text LCD", None),
+    known(0x40, "HDC1080 humidity sensor", Some((0xfe, 0xff, 0x54))),
+    known(0x44, "SHT31 humidity sensor", None),
+    known(0x48, "ADS1115 ADC", None),
+    known(0x51, "PCF8563 RTC", None),
+    known(0x53, "ADXL345 accelerometer", Some((0x00, 0xff, 0xe5))),
+    known(0x62, "PCA9633 RGB backlight", Some((0x0c, 0xff, 0xe0))),
+    known(0x68, "MPU-6050 IMU", Some((0x75, 0x7e, 0x68))),
+    known(0x68, "DS1307/DS3231 RTC", None),
+    known(0x76, "BME280 sensor", Some((0xd0, 0xff, 0x60))),
+    known(0x76, "BMP280 sensor", Some((0xd0, 0xff, 0x58))),
+    known(0x77, "BME280 sensor", Some((0xd0, 0xff, 0x60))),
+];
+
+/// Addresses reserved for general call, CBUS, high speed and 10-bit addressing
+pub fn is_reserved(address: u8) -> bool {
+    !(FIRST_ADDRESS..=LAST_ADDRESS).contains(&address)
+}
+
+/// True if a device acknowledges its address
+pub fn probe<I2C: Read + Write>(i2c: &mut I2C, address: u8) -> bool {
+    if WRITE_ONLY.contains(&address) {
+        // control byte announcing commands, none follow
+        return i2c.write(address, &[0x00]).is_ok();
+    }
+    let mut buffer = [0u8; 1];
+    i2c.read(address, &mut buffer).is_ok()
+}
+
+/// Name of the device at address, None if unknown or not identified
+pub fn identify<I2C: WriteRead>(i2c: &mut I2C, address: u8) -> Option<&'static str> {
+    KNOWN
+        .iter()
+        .filter(|known| known.address == address)
+        .find(|known| match known.id {
+            None => true,
+            Some((register, mask, value)) => {
+                let mut buffer = [0u8; 1];
+                i2c.write_read(address, &[register], &mut buffer).is_ok()
+                    && buffer[0] & mask == value
+            }
+        })
+        .map(|known| known.name)
+}
+
+/// Iterate over the responding devices, skipping reserved addresses
+pub fn scan<I2C: Read + Write + WriteRead>(i2c: &mut I2C) -> Scan<'_, I2C> {
+    Scan {
+        i2c,
+        next: FIRST_ADDRESS,
+    }
+}
+
+pub struct Scan<'a, I2C> {
+    i2c: &'a mut I2C,
+    next: u8,
+}
+
+impl<'a, I2C: Read + Write + WriteRead> Iterator for Scan<'a, I2C> {
+    type Item = Device;
+
+    fn next(&mut self) -> Option<Device> {
+        while self.next <= LAST_ADDRESS {
+            let address = self.next;
+            self.next += 1;
+            if probe(self.i2c, address) {
+                let name = identify(self.i2c, address);
+                return Some(Device { address, name });
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use super::*;
+    use crate::mock::I2cMock;
+    use std::vec::Vec;
+
+    #[test]
+    fn identify_grove_devices() {
+        let mut i2c = I2cMock::new();
+        i2c.registers[0x0c] = 0xe0;
+        i2c.devices.push((0x68, 0x75, 0x68));
+        i2c.devices.push((0x76, 0xd0, 0x58));
+        i2c.devices.push((0x10, 0x00, 0x00));
+        let devices: Vec<Device> = scan(&mut i2c).collect();
+        let found: Vec<(u8, Option<&str>)> = devices
+            .iter()
+            .map(|device| (device.address, device.name))
+            .collect();
+        assert_eq!(
+            found,
+            [
+                (0x10, None),
+                (0x3e, Some("JHD1313 text LCD")),
+                (0x62, Some("PCA9633 RGB backlight")),
+                (0x68, Some("MPU-6050 IMU")),
+                (0x76, Some("BMP280 sensor")),
+            ]
+        );
+        assert!(is_reserved(0x00) && is_reserved(0x78));
+    }
+
+    #[test]
+    fn probe_write_only_lcd() {
+        let mut i2c = I2cMock::new();
+        // the LCD does not acknowledge reads
+        assert!(i2c.read(0x3e, &mut [0u8; 1]).is_err());
+        assert!(probe(&mut i2c, 0x3e));
+        assert_eq!(i2c.writes, [(0x3e, std::vec![0x00])]);
+        assert!(!probe(&mut i2c, 0x3c));
+    }
+
+    #[test]
+    fn fall_back_to_address() {
+        let mut i2c = I2cMock::new();
+        i2c.devices.push((0x68, 0x0f, 0x88));
+        assert_eq!(identify(&mut i2c, 0x68), Some("DS1307/DS3231 RTC"));
+        assert_eq!(identify(&mut i2c, 0x77), None);
+    }
+}
@@ -0,0 +1,391 @@
+//!
+//! Calculation of the STM32G0 I2C `TIMINGR` register
+//!
+//! The register value is derived from the I2C kernel clock, the bus mode,
+//! the rise and fall times of the bus lines and the noise filters, see
+//! RM0444 "I2C timings" and AN4235. It follows the approach of ST's timing
+//! configuration tool: the high period is as short as the mode allows and
+//! the low period takes the rest of the clock period.
+//!
+//! ```ignore
+//! let timing = Parameters::new(rcc.clocks.apb_clk.0, Mode::Fast).compute()?;
+//! let i2c = dp.I2C1.i2c(sda, scl, Config::with_timing(timing.bits()), &mut rcc);
+//! ```
+//!
+//! Fast-mode Plus additionally requires the 20 mA drive of the pins, see
+//! `SYSCFG_CFGR1.I2C_PBx_FMP`.
+
+/// Picoseconds per second
+const PS_PER_S: u64 = 1_000_000_000_000;
+/// Delay of the analog noise filter (ps)
+const ANALOG_FILTER_MIN: u64 = 50_000;
+const ANALOG_FILTER_MAX: u64 = 260_000;
+
+const PRESC_MAX: u64 = 15;
+const SCLDEL_MAX: u64 = 15;
+const SDADEL_MAX: u64 = 15;
+const SCLH_MAX: u64 = 255;
+const SCLL_MAX: u64 = 255;
+
+/// Bus mode
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Mode {
+    /// 100 kHz
+    Standard,
+    /// 400 kHz
+    Fast,
+    /// 1 MHz
+    FastPlus,
+}
+
+/// Characteristics of the SCL and SDA lines per I2C specification (ns)
+struct Spec {
+    frequency: u32,
+    hold_min: u32,
+    valid_max: u32,
+    setup_min: u32,
+    low_min: u32,
+    high_min: u32,
+    rise_max: u32,
+    fall_max: u32,
+}
+
+impl Mode {
+    /// Nominal SCL frequency (Hz)
+    pub const fn frequency(self) -> u32 {
+        self.spec().frequency
+    }
+
+    const fn spec(self) -> Spec {
+        match self {
+            Mode::Standard => Spec {
+                frequency: 100_000,
+                hold_min: 0,
+                valid_max: 3450,
+                setup_min: 250,
+                low_min: 4700,
+                high_min: 4000,
+                rise_max: 1000,
+                fall_max: 300,
+            },
+            Mode::Fast => Spec {
+                frequency: 400_000,
+                hold_min: 0,
+                valid_max: 900,
+                setup_min: 100,
+                low_min: 1300,
+                high_min: 600,
+                rise_max: 300,
+                fall_max: 300,
+            },
+            Mode::FastPlus => Spec {
+                frequency: 1_000_000,
+                hold_min: 0,
+                valid_max: 450,
+                setup_min: 50,
+                low_min: 500,
+                high_min: 260,
+                rise_max: 120,
+                fall_max: 120,
+            },
+        }
+    }
+}
+
+/// Violated timing constraint
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum TimingError {
+    /// Rise time exceeds the maximum of the mode
+    RiseTime,
+    /// Fall time exceeds the maximum of the mode
+    FallTime,
+    /// SCLDEL is too short for the data setup time
+    SetupTime,
+    /// SDADEL is too short for the data hold time
+    HoldTime,
+    /// SDADEL is too long for the data valid time
+    ValidTime,
+    /// SCL low period too short
+    LowPeriod,
+    /// SCL high period too short
+    HighPeriod,
+    /// The kernel clock is too slow for the filters and periods
+    KernelClock,
+    /// The SCL frequency is more than 20% off the nominal frequency
+    Frequency,
+    /// No register values satisfy the constraints
+    NoSolution,
+}
+
+/// Fields of the `TIMINGR` register
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Timing {
+    pub presc: u8,
+    pub scldel: u8,
+    pub sdadel: u8,
+    pub sclh: u8,
+    pub scll: u8,
+}
+
+impl Timing {
+    pub const fn from_bits(bits: u32) -> Self {
+        Timing {
+            presc: (bits >> 28) as u8 & 0x0f,
+            scldel: (bits >> 20) as u8 & 0x0f,
+            sdadel: (bits >> 16) as u8 & 0x0f,
+            sclh: (bits >> 8) as u8,
+            scll: bits as u8,
+        }
+    }
+
+    /// Register value, e.g. for `Config::with_timing`
+    pub const fn bits(&self) -> u32 {
+        (self.presc as u32) << 28
+            | (self.scldel as u32) << 20
+            | (self.sdadel as u32) << 16
+            | (self.sclh as u32) << 8
+            | self.scll as u32
+    }
+}
+
+/// Bus parameters a timing is computed for
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Parameters {
+    kernel_clock: u32,
+    mode: Mode,
+    rise: u32,
+    fall: u32,
+    analog_filter: bool,
+    digital_filter: u8,
+}
+
+impl Parameters {
+    /// Parameters with ideal edges and the analog filter enabled, as the HAL defaults
+    ///
+    /// Args:
+    /// * kernel_clock - I2C kernel clock (Hz), PCLK by default
+    /// * mode - bus mode
+    pub const fn new(kernel_clock: u32, mode: Mode) -> Self {
+        Parameters {
+            kernel_clock,
+            mode,
+            rise: 0,
+            fall: 0,
+            analog_filter: true,
+            digital_filter: 0,
+        }
+    }
+
+    /// Rise and fall time of the bus lines (ns), depend on pull-ups and bus capacitance
+    pub const fn with_rise_fall(mut self, rise: u32, fall: u32) -> Self {
+        self.rise = rise;
+        self.fall = fall;
+        self
+    }
+
+    pub const fn without_analog_filter(mut self) -> Self {
+        self.analog_filter = false;
+        self
+    }
+
+    /// Digital filter suppressing spikes up to cycles kernel clock periods
+    pub fn with_digital_filter(mut self, cycles: u8) -> Self {
+        if cycles > 15 {
+            panic!("Digital filter must be in range 0..15");
+        }
+        self.digital_filter = cycles;
+        self
+    }
+
+    /// Compute the register values
+    pub fn compute(&self) -> Result<Timing, TimingError> {
+        self.check_edges()?;
+        let spec = self.mode.spec();
+        let clock = self.clock_period();
+        let sync = self.sync_delay();
+        let (rise, fall) = (ns(self.rise), ns(self.fall));
+        let period = (PS_PER_S + spec.frequency as u64 / 2) / spec.frequency as u64;
+        for presc in 0..=PRESC_MAX {
+            let tick = (presc + 1) * clock;
+            let scldel = (rise + ns(spec.setup_min)).div_ceil(tick).saturating_sub(1);
+            let sdadel = self.sdadel_min().div_ceil(tick);
+            let sclh = ns(spec.high_min)
+                .saturating_sub(sync)
+                .div_ceil(tick)
+                .saturating_sub(1);
+            let high = (sclh + 1) * tick + sync;
+            // the low period takes the rest of the clock period
+            let low = period.saturating_sub(high + rise + fall + sync);
+            let scll = ((low + tick / 2) / tick)
+                .max(ns(spec.low_min).saturating_sub(sync).div_ceil(tick))
+                .saturating_sub(1);
+            if scldel > SCLDEL_MAX || sdadel > SDADEL_MAX || sclh > SCLH_MAX || scll > SCLL_MAX {
+                continue;
+            }
+            let timing = Timing {
+                presc: presc as u8,
+                scldel: scldel as u8,
+                sdadel: sdadel as u8,
+                sclh: sclh as u8,
+                scll: scll as u8,
+            };
+            if self.validate(&timing).is_ok() {
+                return Ok(timing);
+            }
+        }
+        Err(TimingError::NoSolution)
+    }
+
+    /// Check register values against the I2C specification
+    ///
+    /// Returns
+    /// * the resulting SCL frequency (Hz) or the first violated constraint
+    pub fn validate(&self, timing: &Timing) -> Result<u32, TimingError> {
+        self.check_edges()?;
+        let spec = self.mode.spec();
+        let clock = self.clock_period();
+        let sync = self.sync_delay();
+        let (rise, fall) = (ns(self.rise), ns(self.fall));
+        let tick = (timing.presc as u64 + 1) * clock;
+
+        if (timing.scldel as u64 + 1) * tick < rise + ns(spec.setup_min) {
+            return Err(TimingError::SetupTime);
+        }
+        let sdadel = timing.sdadel as u64 * tick;
+        if sdadel < self.sdadel_min() {
+            return Err(TimingError::HoldTime);
+        }
+        let filter_max = if self.analog_filter {
+            ANALOG_FILTER_MAX
+        } else {
+            0
+        };
+        // as ST, a negative margin at slow kernel clocks leaves SDADEL 0 valid
+        let delays = rise + filter_max + (self.digital_filter as u64 + 4) * clock;
+        if sdadel > ns(spec.valid_max).saturating_sub(delays) {
+            return Err(TimingError::ValidTime);
+        }
+        let low = (timing.scll as u64 + 1) * tick + sync;
+        let high = (timing.sclh as u64 + 1) * tick + sync;
+        if low < ns(spec.low_min) {
+            return Err(TimingError::LowPeriod);
+        }
+        if high < ns(spec.high_min) {
+            return Err(TimingError::HighPeriod);
+        }
+        // the peripheral samples SCL with the kernel clock
+        let filters = sync - 2 * clock;
+        if 4 * clock >= low - filters || clock >= high {
+            return Err(TimingError::KernelClock);
+        }
+        let frequency = (PS_PER_S / (low + high + rise + fall)) as u32;
+        let nominal = spec.frequency;
+        if frequency < nominal / 10 * 8 || frequency > nominal / 10 * 12 {
+            return Err(TimingError::Frequency);
+        }
+        Ok(frequency)
+    }
+
+    fn check_edges(&self) -> Result<(), TimingError> {
+        let spec = self.mode.spec();
+        if self.rise > spec.rise_max {
+            return Err(TimingError::RiseTime);
+        }
+        if self.fall > spec.fall_max {
+            return Err(TimingError::FallTime);
+        }
+        Ok(())
+    }
+
+    /// Kernel clock period (ps)
+    fn clock_period(&self) -> u64 {
+        PS_PER_S / self.kernel_clock as u64
+    }
+
+    /// Delay of the SCL synchronization, analog and digital filter (ps)
+    fn sync_delay(&self) -> u64 {
+        let analog = if self.analog_filter {
+            ANALOG_FILTER_MIN
+        } else {
+            0
+        };
+        analog + (self.digital_filter as u64 + 2) * self.clock_period()
+    }
+
+    /// Minimal SDADEL delay for the data hold time (ps)
+    fn sdadel_min(&self) -> u64 {
+        let spec = self.mode.spec();
+        let analog = if self.analog_filter {
+            ANALOG_FILTER_MIN
+        } else {
+            0
+        };
+        let delays = analog + (self.digital_filter as u64 + 3) * self.clock_period();
+        (ns(self.fall) + ns(spec.hold_min)).saturating_sub(delays)
+    }
+}
+
+const fn ns(value: u32) -> u64 {
+    value as u64 * 1000
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reference_values() {
+        // STM32CubeMX, analog filter on, rise and fall time 0 ns
+        let references = [
+            (16_000_000, Mode::Standard, 0x0030_3d5b),
+            (16_000_000, Mode::Fast, 0x0010_061a),
+            (16_000_000, Mode::FastPlus, 0x0000_0107),
+            (64_000_000, Mode::Standard, 0x1070_7dbc),
+            (64_000_000, Mode::Fast, 0x0060_2173),
+            (64_000_000, Mode::FastPlus, 0x0030_0b29),
+        ];
+        for (clock, mode, bits) in references.iter() {
+            let timing = Parameters::new(*clock, *mode).compute().unwrap();
+            assert_eq!(timing.bits(), *bits);
+            assert_eq!(Timing::from_bits(*bits), timing);
+        }
+        let parameters = Parameters::new(16_000_000, Mode::Standard);
+        assert_eq!(
+            parameters.validate(&Timing::from_bits(0x0030_3d5b)),
+            Ok(100_250)
+        );
+    }
+
+    #[test]
+    fn rise_and_fall_times() {
+        let parameters = Parameters::new(16_000_000, Mode::Fast).with_rise_fall(250, 100);
+        let timing = parameters.compute().unwrap();
+        let frequency = parameters.validate(&timing).unwrap();
+        assert!(frequency > 390_000 && frequency <= 410_000);
+        assert_eq!(
+            Parameters::new(16_000_000, Mode::FastPlus)
+                .with_rise_fall(400, 20)
+                .compute(),
+            Err(TimingError::RiseTime)
+        );
+    }
+
+    #[test]
+    fn reject_spec_violations() {
+        let parameters = Parameters::new(16_000_000, Mode::FastPlus);
+        let too_short_high = Timing::from_bits(0x0000_0007);
+        assert_eq!(
+            parameters.validate(&too_short_high),
+            Err(TimingError::HighPeriod)
+        );
+        // 0x2020_151b is a 100 kHz timing
+        assert_eq!(
+            parameters.validate(&Timing::from_bits(0x2020_151b)),
+            Err(TimingError::Frequency)
+        );
+        assert_eq!(
+            Parameters::new(2_000_000, Mode::FastPlus).compute(),
+            Err(TimingError::NoSolution)
+        );
+    }
+}
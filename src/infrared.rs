@@ -0,0 +1,50 @@
+//!
+//! Infrared remote control protocols
+//!
+//! RC5 frames are Manchester (bi-phase) coded; they are decoded from
+//! periodic samples by the `manchester_code` crate or from the durations
+//! between edges. NEC frames are pulse distance coded, RC6 frames are
+//! Manchester coded with a bit of double length and SIRC frames are pulse
+//! width coded; they are decoded from the durations between edges, which
+//! are timestamped by timer input capture.
+//!
+//! All edge based decoders implement `IrDecoder`; `receiver::IrReceiver`
+//! runs them in parallel and detects the protocol. `transmitter::IrTransmitter`
+//! sends queued frames of any protocol from a timer interrupt,
+//! `dma::DmaTransmitter` streams a whole frame by DMA.
+
+pub mod capture;
+pub mod dma;
+pub mod key;
+pub mod nec;
+pub mod rc5;
+pub mod rc6;
+pub mod receiver;
+pub mod sirc;
+pub mod transmitter;
+
+/// Decoder of marks and spaces into frames of a protocol
+pub trait IrDecoder {
+    type Frame;
+
+    /// Process the duration of a mark (carrier true) or space that just
+    /// ended, Some(frame) once a frame is completely received
+    fn next(&mut self, carrier: bool, duration_us: u32) -> Option<Self::Frame>;
+
+    /// Forget a partially received frame
+    fn reset(&mut self);
+}
+
+/// Accepted deviation of a mark or space duration in percent
+const TOLERANCE_PERCENT: u32 = 30;
+
+/// True if a duration matches the nominal one within the tolerance
+fn within(duration_us: u32, nominal_us: u32) -> bool {
+    let tolerance = nominal_us * TOLERANCE_PERCENT / 100;
+    (nominal_us - tolerance..=nominal_us + tolerance).contains(&duration_us)
+}
+
+/// Number of units of a duration, rounded
+fn units(duration_us: u32, unit_us: u32) -> u32 {
+    (duration_us + unit_us / 2) / unit_us
+}
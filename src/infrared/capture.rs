@@ -0,0 +1,394 @@
+//!
+//! Edge timestamps of an infrared receiver by timer input capture
+//!
+//! TIM2 counts µs with its 32 bit counter. The receiver output on channel 2
+//! (e.g. PB3) is captured on the rising edge by channel 2 and on the falling
+//! edge by channel 1, so both edges are timestamped by hardware. The capture
+//! interrupt pushes the edges into an `EdgeBuffer`; the main loop turns them
+//! into marks and spaces with a `PulseStream`, which feed the decoders.
+//!
+//! ```ignore
+//! #[interrupt]
+//! fn TIM2() {
+//!     free(|cs| {
+//!         let mut edges = EDGES.borrow(cs).borrow_mut();
+//!         CAPTURE.borrow(cs).borrow_mut().as_mut().unwrap().read(&mut edges);
+//!     });
+//! }
+//!
+//! while let Some(edge) = free(|cs| EDGES.borrow(cs).borrow_mut().pop()) {
+//!     if let Some((carrier, duration)) = stream.edge(edge) {
+//!         nec.next(carrier, duration);
+//!     }
+//! }
+//! ```
+
+use crate::hal::rcc::{Enable, Rcc, Reset};
+use crate::hal::stm32::TIM2;
+use crate::hal::timer::{pins::TimerPin, Channel2};
+
+/// Input filter, 8 samples at the timer clock
+const FILTER: u8 = 0b0011;
+
+/// Edge of the receiver output
+#[derive(Clone, Copy, PartialEq, Debug, defmt::Format)]
+pub struct Edge {
+    /// Timestamp in µs, wrapping
+    pub time: u32,
+    pub rising: bool,
+}
+
+/// Ring buffer of edges, filled by the capture interrupt
+pub struct EdgeBuffer<const N: usize> {
+    edges: [Edge; N],
+    first: usize,
+    len: usize,
+    lost: u32,
+}
+
+impl<const N: usize> Default for EdgeBuffer<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const N: usize> EdgeBuffer<N> {
+    pub const fn new() -> Self {
+        EdgeBuffer {
+            edges: [Edge {
+                time: 0,
+                rising: false,
+            }; N],
+            first: 0,
+            len: 0,
+            lost: 0,
+        }
+    }
+
+    /// Append an edge, it is lost if the buffer is full
+    pub fn push(&mut self, edge: Edge) {
+        if self.len == N {
+            self.lost = self.lost.wrapping_add(1);
+        } else {
+            self.edges[(self.first + self.len) % N] = edge;
+            self.len += 1;
+        }
+    }
+
+    /// Oldest edge
+    pub fn pop(&mut self) -> Option<Edge> {
+        if self.len == 0 {
+            None
+        } else {
+            let edge = self.edges[self.first];
+            self.first = (self.first + 1) % N;
+            self.len -= 1;
+            Some(edge)
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Number of edges lost due to a full buffer or a missed capture
+    pub fn lost(&self) -> u32 {
+        self.lost
+    }
+}
+
+/// Marks and spaces of an active low receiver from its edges
+///
+/// A mark lasts from a falling to a rising edge. The space after the last
+/// mark is reported by `timeout` once it exceeds the pause duration, so
+/// decoders see the end of a frame without waiting for the next one.
+pub struct PulseStream {
+    pause_us: u32,
+    last: Option<Edge>,
+    paused: bool,
+}
+
+impl PulseStream {
+    /// Stream reporting spaces longer than pause_us by timeout
+    pub const fn new(pause_us: u32) -> Self {
+        PulseStream {
+            pause_us,
+            last: None,
+            paused: false,
+        }
+    }
+
+    /// Process an edge
+    ///
+    /// Returns:
+    /// * Some((carrier, duration)) - mark or space ended by the edge, in µs
+    pub fn edge(&mut self, edge: Edge) -> Option<(bool, u32)> {
+        let pulse = match self.last {
+            // an edge in between is lost
+            Some(last) if last.rising == edge.rising => None,
+            Some(_) if !edge.rising && self.paused => None,
+            Some(last) => Some((edge.rising, edge.time.wrapping_sub(last.time))),
+            None => None,
+        };
+        self.last = Some(edge);
+        self.paused = false;
+        pulse
+    }
+
+    /// Report the space since the last mark, once it lasts longer than the pause
+    pub fn timeout(&mut self, now: u32) -> Option<(bool, u32)> {
+        match self.last {
+            Some(last) if last.rising && !self.paused => {
+                let duration = now.wrapping_sub(last.time);
+                if duration > self.pause_us {
+                    self.paused = true;
+                    Some((false, duration))
+                } else {
+                    None
+                }
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Marks and spaces of an active low receiver from periodic samples
+///
+/// An alternative to input capture; the resolution is the sample period,
+/// which should be 50 µs or less for RC6.
+pub struct SampledPulses {
+    period_us: u32,
+    pause_us: u32,
+    level: bool,
+    duration: u32,
+    paused: bool,
+}
+
+impl SampledPulses {
+    /// Sampled every period_us, spaces longer than pause_us are reported
+    /// once they exceed it
+    pub const fn new(period_us: u32, pause_us: u32) -> Self {
+        SampledPulses {
+            period_us,
+            pause_us,
+            level: true,
+            duration: 0,
+            paused: true,
+        }
+    }
+
+    /// Process the level of the receiver output
+    ///
+    /// Returns:
+    /// * Some((carrier, duration)) - mark or space ended, in µs
+    pub fn sample(&mut self, high: bool) -> Option<(bool, u32)> {
+        let mut pulse = None;
+        if high != self.level {
+            if !self.paused {
+                pulse = Some((!self.level, self.duration));
+            }
+            self.level = high;
+            self.duration = 0;
+            self.paused = false;
+        }
+        self.duration = self.duration.saturating_add(self.period_us);
+        if self.level && !self.paused && self.duration > self.pause_us {
+            self.paused = true;
+            pulse = Some((false, self.duration));
+        }
+        pulse
+    }
+}
+
+/// Both edges of TIM2 channel 2 captured with 1 µs resolution
+pub struct InputCapture {
+    tim: TIM2,
+}
+
+impl InputCapture {
+    pub fn tim2<PIN: TimerPin<TIM2, Channel = Channel2>>(
+        tim: TIM2,
+        pin: PIN,
+        rcc: &mut Rcc,
+    ) -> Self {
+        TIM2::enable(rcc);
+        TIM2::reset(rcc);
+        pin.setup();
+
+        let psc = rcc.clocks.apb_tim_clk.0 / 1_000_000 - 1;
+        tim.psc.write(|w| unsafe { w.psc().bits(psc as u16) });
+        tim.arr.write(|w| unsafe { w.bits(u32::MAX) });
+        // channel 1 on TI2 (indirect), channel 2 on TI2 (direct)
+        tim.ccmr1_input().write(|w| unsafe {
+            w.cc1s()
+                .bits(0b10)
+                .ic1f()
+                .bits(FILTER)
+                .cc2s()
+                .bits(0b01)
+                .ic2f()
+                .bits(FILTER)
+        });
+        // channel 1 on the falling edge, channel 2 on the rising edge
+        tim.ccer
+            .write(|w| w.cc1p().set_bit().cc1e().set_bit().cc2e().set_bit());
+        tim.egr.write(|w| w.ug().set_bit());
+        tim.cr1.modify(|_, w| w.cen().set_bit());
+        InputCapture { tim }
+    }
+
+    /// Interrupt on every edge
+    pub fn listen(&mut self) {
+        self.tim
+            .dier
+            .modify(|_, w| w.cc1ie().set_bit().cc2ie().set_bit());
+    }
+
+    pub fn unlisten(&mut self) {
+        self.tim
+            .dier
+            .modify(|_, w| w.cc1ie().clear_bit().cc2ie().clear_bit());
+    }
+
+    /// Current timestamp in µs
+    pub fn now(&self) -> u32 {
+        self.tim.cnt.read().bits()
+    }
+
+    /// Move the captured edges to the buffer in chronological order,
+    /// to be called by the interrupt handler
+    pub fn read<const N: usize>(&mut self, edges: &mut EdgeBuffer<N>) {
+        let sr = self.tim.sr.read();
+        if sr.cc1of().bit_is_set() || sr.cc2of().bit_is_set() {
+            edges.lost = edges.lost.wrapping_add(1);
+            self.tim
+                .sr
+                .modify(|_, w| w.cc1of().clear_bit().cc2of().clear_bit());
+        }
+        // reading the capture register clears the interrupt flag
+        let falling = if sr.cc1if().bit_is_set() {
+            Some(Edge {
+                time: self.tim.ccr1.read().bits(),
+                rising: false,
+            })
+        } else {
+            None
+        };
+        let rising = if sr.cc2if().bit_is_set() {
+            Some(Edge {
+                time: self.tim.ccr2.read().bits(),
+                rising: true,
+            })
+        } else {
+            None
+        };
+        let now = self.now();
+        let (first, second) = match (falling, rising) {
+            (Some(f), Some(r)) if now.wrapping_sub(r.time) > now.wrapping_sub(f.time) => {
+                (rising, falling)
+            }
+            _ => (falling, rising),
+        };
+        for edge in [first, second].iter().flatten() {
+            edges.push(*edge);
+        }
+    }
+
+    /// Stop the timer and return it
+    pub fn release(self) -> TIM2 {
+        self.tim.cr1.modify(|_, w| w.cen().clear_bit());
+        self.tim
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use super::*;
+    use crate::infrared::nec::{NecCode, NecDecoder, NecEncoder, NecFrame};
+    use std::vec::Vec;
+
+    fn edge(time: u32, rising: bool) -> Edge {
+        Edge { time, rising }
+    }
+
+    #[test]
+    fn ring_buffer() {
+        let mut edges = EdgeBuffer::<3>::new();
+        for time in 0..4 {
+            edges.push(edge(time, time % 2 == 1));
+        }
+        assert_eq!(edges.len(), 3);
+        assert_eq!(edges.lost(), 1);
+        assert_eq!(edges.pop(), Some(edge(0, false)));
+        edges.push(edge(4, false));
+        let times: Vec<u32> = core::iter::from_fn(|| edges.pop())
+            .map(|e| e.time)
+            .collect();
+        assert_eq!(times, [1, 2, 4]);
+        assert!(edges.is_empty());
+    }
+
+    #[test]
+    fn marks_spaces_and_timeout() {
+        let mut stream = PulseStream::new(10_000);
+        assert_eq!(stream.edge(edge(u32::MAX - 99, false)), None);
+        // the counter wraps within the mark
+        assert_eq!(stream.edge(edge(462, true)), Some((true, 562)));
+        assert_eq!(stream.timeout(1000), None);
+        assert_eq!(stream.edge(edge(1024, false)), Some((false, 562)));
+        // lost rising edge
+        assert_eq!(stream.edge(edge(2000, false)), None);
+        assert_eq!(stream.edge(edge(2562, true)), Some((true, 562)));
+        assert_eq!(stream.timeout(20_000), Some((false, 17_438)));
+        assert_eq!(stream.timeout(30_000), None);
+        // the pause is not reported twice
+        assert_eq!(stream.edge(edge(40_000, false)), None);
+        assert_eq!(stream.edge(edge(49_000, true)), Some((true, 9000)));
+    }
+
+    #[test]
+    fn sampled_marks_and_spaces() {
+        let mut pulses = SampledPulses::new(50, 10_000);
+        let levels = [
+            (true, 400),
+            (false, 12),
+            (true, 11),
+            (false, 34),
+            (true, 201),
+        ];
+        let received: Vec<(bool, u32)> = levels
+            .iter()
+            .flat_map(|&(high, samples)| core::iter::repeat(high).take(samples))
+            .filter_map(|high| pulses.sample(high))
+            .collect();
+        assert_eq!(
+            received,
+            [(true, 600), (false, 550), (true, 1700), (false, 10_050)]
+        );
+    }
+
+    #[test]
+    fn decode_captured_edges() {
+        let frame = NecFrame::new(0x04, 0x08);
+        let mut edges = EdgeBuffer::<80>::new();
+        let mut time = 123_456;
+        for (carrier, duration) in NecEncoder::new(frame) {
+            edges.push(edge(time, !carrier));
+            time += duration;
+        }
+        edges.push(edge(time, true));
+        let mut stream = PulseStream::new(10_000);
+        let mut decoder = NecDecoder::new();
+        let codes: Vec<NecCode> = core::iter::from_fn(|| edges.pop())
+            .filter_map(|edge| stream.edge(edge))
+            .filter_map(|(carrier, duration)| decoder.next(carrier, duration))
+            .collect();
+        assert_eq!(codes, [NecCode::Frame(frame)]);
+    }
+}
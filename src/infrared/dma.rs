@@ -0,0 +1,237 @@
+//!
+//! Transmitter of frames streamed by DMA
+//!
+//! A frame is precomputed into one compare value of the carrier PWM per unit
+//! of its protocol (the half bit of RC5 and RC6, 562 µs for NEC and 600 µs
+//! for SIRC): the duty of a mark or zero for a space. TIM6 overflows once per
+//! unit and its update DMA request writes the next value to the compare
+//! register of TIM3 channel 1, so the frame goes out without any CPU
+//! involvement. The transfer complete interrupt of the DMA channel ends the
+//! frame.
+//!
+//! ```ignore
+//! #[interrupt]
+//! fn DMA_CHANNEL1() {
+//!     free(|cs| TRANSMITTER.borrow(cs).borrow_mut().as_mut().unwrap().on_interrupt());
+//! }
+//!
+//! let buffer = unsafe { &mut *core::ptr::addr_of_mut!(BUFFER) };
+//! let transmitter = DmaTransmitter::tim6(dp.TIM6, carrier, dma.ch1, buffer, &mut rcc)
+//!     .with_callback(sent);
+//! ```
+
+use core::convert::Infallible;
+
+use crate::hal::dma::{Channel, Direction, Event, Priority, WordSize};
+use crate::hal::dmamux::DmaMuxIndex;
+use crate::hal::rcc::{Enable, Rcc, Reset};
+use crate::hal::stm32::TIM6;
+use crate::hal::timer::Channel1;
+
+use super::receiver::IrFrame;
+use super::transmitter::{Carrier, CarrierConfig, Pulses, PwmCarrier};
+use super::units;
+
+/// Buffer length for the longest frame (NEC) and the final space
+pub const BUFFER_UNITS: usize = 160;
+
+/// Sends a frame at a time by DMA
+pub struct DmaTransmitter<CH> {
+    tim: TIM6,
+    carrier: PwmCarrier<Channel1>,
+    channel: CH,
+    buffer: &'static mut [u16],
+    busy: bool,
+    completed: u32,
+    callback: Option<fn()>,
+}
+
+impl<CH: Channel> DmaTransmitter<CH> {
+    /// Transmitter with TIM6 as bit clock and the carrier on TIM3 channel 1,
+    /// the interrupt of the DMA channel must be enabled
+    ///
+    /// The buffer must hold at least `BUFFER_UNITS` values.
+    pub fn tim6(
+        tim: TIM6,
+        carrier: PwmCarrier<Channel1>,
+        mut channel: CH,
+        buffer: &'static mut [u16],
+        rcc: &mut Rcc,
+    ) -> Self {
+        assert!(
+            buffer.len() >= BUFFER_UNITS,
+            "buffer too small for the frames"
+        );
+        TIM6::enable(rcc);
+        TIM6::reset(rcc);
+        let psc = rcc.clocks.apb_tim_clk.0 / 1_000_000 - 1;
+        tim.psc.write(|w| unsafe { w.psc().bits(psc as u16) });
+        tim.dier.write(|w| w.ude().set_bit());
+
+        channel.select_peripheral(DmaMuxIndex::TIM6_UP);
+        channel.set_direction(Direction::FromMemory);
+        channel.set_word_size(WordSize::BITS16);
+        channel.set_priority_level(Priority::High);
+        channel.listen(Event::TransferComplete);
+        DmaTransmitter {
+            tim,
+            carrier,
+            channel,
+            buffer,
+            busy: false,
+            completed: 0,
+            callback: None,
+        }
+    }
+
+    /// Function called by `on_interrupt` once a frame is sent
+    pub fn with_callback(mut self, callback: fn()) -> Self {
+        self.callback = Some(callback);
+        self
+    }
+
+    /// Start sending a frame with the carrier of its protocol
+    ///
+    /// Returns:
+    /// * Err(WouldBlock) - the previous frame is still sent
+    pub fn send(&mut self, frame: IrFrame) -> nb::Result<(), Infallible> {
+        self.send_with_carrier(frame, CarrierConfig::protocol(frame.protocol()))
+    }
+
+    /// Start sending a frame with its own carrier
+    pub fn send_with_carrier(
+        &mut self,
+        frame: IrFrame,
+        carrier: CarrierConfig,
+    ) -> nb::Result<(), Infallible> {
+        if self.busy {
+            return Err(nb::Error::WouldBlock);
+        }
+        self.carrier.configure(carrier);
+        let (address, mark) = self.carrier.dma_target();
+        let unit_us = frame.protocol().unit_us();
+        let len = fill(Pulses::new(&frame, false), unit_us, mark, self.buffer);
+
+        self.channel.set_peripheral_address(address, false);
+        self.channel
+            .set_memory_address(self.buffer.as_ptr() as u32, true);
+        self.channel.set_transfer_length(len as u16);
+        self.channel.enable();
+        self.busy = true;
+
+        self.tim
+            .arr
+            .write(|w| unsafe { w.arr().bits((unit_us - 1) as u16) });
+        // the update event requests the first value at once
+        self.tim.egr.write(|w| w.ug().set_bit());
+        self.tim.cr1.modify(|_, w| w.cen().set_bit());
+        Ok(())
+    }
+
+    /// True while a frame is sent
+    pub fn is_busy(&self) -> bool {
+        self.busy
+    }
+
+    /// Number of frames sent
+    pub fn completed(&self) -> u32 {
+        self.completed
+    }
+
+    /// End the frame, to be called by the interrupt handler of the DMA channel
+    pub fn on_interrupt(&mut self) {
+        if !self.channel.event_occurred(Event::TransferComplete) {
+            return;
+        }
+        self.channel.clear_event(Event::Any);
+        self.channel.disable();
+        self.tim.cr1.modify(|_, w| w.cen().clear_bit());
+        // the final space is written already
+        self.carrier.off();
+        self.busy = false;
+        self.completed = self.completed.wrapping_add(1);
+        if let Some(callback) = self.callback {
+            callback();
+        }
+    }
+
+    /// Stop and return the timer, the carrier and the DMA channel
+    pub fn release(mut self) -> (TIM6, PwmCarrier<Channel1>, CH) {
+        self.channel.disable();
+        self.tim.cr1.modify(|_, w| w.cen().clear_bit());
+        self.carrier.off();
+        (self.tim, self.carrier, self.channel)
+    }
+}
+
+/// Write the compare value of every unit of the marks and spaces to the
+/// buffer, followed by a space which turns the carrier off
+///
+/// Returns the number of values
+fn fill(
+    pulses: impl Iterator<Item = (bool, u32)>,
+    unit_us: u32,
+    mark: u16,
+    buffer: &mut [u16],
+) -> usize {
+    let mut len = 0;
+    for (carrier, duration) in pulses.chain(core::iter::once((false, unit_us))) {
+        let value = if carrier { mark } else { 0 };
+        for _ in 0..units(duration, unit_us) {
+            assert!(len < buffer.len(), "buffer too small for the frame");
+            buffer[len] = value;
+            len += 1;
+        }
+    }
+    len
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::infrared::nec::{NecCode, NecFrame};
+    use crate::infrared::rc5::Rc5Frame;
+    use crate::infrared::receiver::Protocol;
+
+    #[test]
+    fn units_of_a_frame() {
+        let mut buffer = [0xffff; BUFFER_UNITS];
+        let frame = IrFrame::Rc5(Rc5Frame::new(0, 1, true));
+        let len = fill(
+            Pulses::new(&frame, false),
+            Protocol::Rc5.unit_us(),
+            100,
+            &mut buffer,
+        );
+        // the leading space of the start bit is not sent
+        assert_eq!(len, 27 + 1);
+        assert_eq!(buffer[..6], [100, 0, 100, 0, 100, 100]);
+        assert_eq!(buffer[len - 1], 0);
+
+        // the longest frame
+        let frame = IrFrame::Nec(NecCode::Frame(NecFrame::extended(0xffff, 0xff)));
+        let len = fill(
+            Pulses::new(&frame, false),
+            Protocol::Nec.unit_us(),
+            100,
+            &mut buffer,
+        );
+        // 24 ones and 8 zeros, the final mark and space
+        assert_eq!(len, 16 + 8 + 24 * 4 + 8 * 2 + 1 + 1);
+        assert!(buffer[..16].iter().all(|&value| value == 100));
+        assert_eq!(buffer[16..25], [0, 0, 0, 0, 0, 0, 0, 0, 100]);
+    }
+
+    #[test]
+    #[should_panic(expected = "buffer too small")]
+    fn buffer_too_small() {
+        let mut buffer = [0; 20];
+        let frame = IrFrame::Rc5(Rc5Frame::new(0, 1, true));
+        fill(
+            Pulses::new(&frame, false),
+            Protocol::Rc5.unit_us(),
+            100,
+            &mut buffer,
+        );
+    }
+}
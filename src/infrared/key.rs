@@ -0,0 +1,373 @@
+//!
+//! Key events of infrared remote controls
+//!
+//! Remotes repeat a frame while a key is held, RC5 every 114 ms. A new key
+//! press is told apart from a repeat by the toggle bit, which flips with
+//! every press. `KeyTracker` turns the received frames into `Pressed`,
+//! rate limited `Repeated`, `LongPress` and `Released` events. A key is
+//! released if no frame arrives for `release_after` ms.
+//!
+//! Timestamps are ms of a free running, wrapping counter.
+//!
+//! ```ignore
+//! if let Some(datagram) = receiver.next(infrared.is_high().unwrap()) {
+//!     if let Ok(frame) = Rc5Frame::from_datagram(&datagram) {
+//!         keys.rc5(now, &frame);
+//!     }
+//! }
+//! while let Some(event) = keys.poll(now) {
+//!     defmt::println!("{}", event);
+//! }
+//! ```
+
+use super::nec::NecCode;
+use super::rc5::{Rc5Frame, REPEAT_PERIOD_MS};
+use super::rc6::Rc6Frame;
+use super::receiver::IrFrame;
+use super::sirc::SircFrame;
+
+/// Key of a remote, identified by device address and command
+#[derive(Clone, Copy, PartialEq, Debug, defmt::Format)]
+pub struct KeyCode {
+    pub address: u16,
+    pub command: u8,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug, defmt::Format)]
+pub enum KeyEvent {
+    Pressed(KeyCode),
+    /// Key is still held, emitted at most every `repeat_interval` ms
+    Repeated(KeyCode),
+    /// Key is held for `long_press_after` ms, emitted once per press
+    LongPress(KeyCode),
+    Released(KeyCode),
+}
+
+/// Key currently held
+struct Held {
+    code: KeyCode,
+    toggle: bool,
+    pressed_at: u32,
+    last_frame: u32,
+    last_event: u32,
+    long_press: bool,
+}
+
+pub struct KeyTracker {
+    release_after: u32,
+    repeat_delay: u32,
+    repeat_interval: u32,
+    long_press_after: u32,
+    held: Option<Held>,
+    pending: [Option<KeyEvent>; 2],
+}
+
+impl Default for KeyTracker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl KeyTracker {
+    /// Tracker for RC5 timing, one lost repeat frame does not release a key
+    ///
+    /// Repeats start after 500 ms with at most 5 events per second, a long
+    /// press is reported after 1 s.
+    pub const fn new() -> Self {
+        KeyTracker {
+            release_after: 2 * REPEAT_PERIOD_MS + REPEAT_PERIOD_MS / 2,
+            repeat_delay: 500,
+            repeat_interval: 200,
+            long_press_after: 1000,
+            held: None,
+            pending: [None; 2],
+        }
+    }
+
+    /// Release a key if no frame arrives for that many ms
+    pub fn with_release_after(mut self, ms: u32) -> Self {
+        self.release_after = ms;
+        self
+    }
+
+    /// First repeat after delay ms, further repeats at most every interval ms
+    pub fn with_repeat(mut self, delay: u32, interval: u32) -> Self {
+        self.repeat_delay = delay;
+        self.repeat_interval = interval;
+        self
+    }
+
+    /// Report a long press after that many ms, 0 disables it
+    pub fn with_long_press(mut self, ms: u32) -> Self {
+        self.long_press_after = ms;
+        self
+    }
+
+    /// Currently held key
+    pub fn held(&self) -> Option<KeyCode> {
+        self.held.as_ref().map(|held| held.code)
+    }
+
+    /// Report a received frame, poll the resulting events before the next one
+    ///
+    /// Args:
+    /// * now - timestamp in ms
+    /// * code - key the frame is for
+    /// * toggle - toggle bit of the frame, a protocol without toggle bit
+    ///   reports the same value for all frames
+    pub fn frame(&mut self, now: u32, code: KeyCode, toggle: bool) {
+        self.expire(now);
+        if let Some(held) = self.held.as_mut() {
+            if held.code == code && held.toggle == toggle {
+                held.last_frame = now;
+                if now.wrapping_sub(held.pressed_at) >= self.repeat_delay
+                    && now.wrapping_sub(held.last_event) >= self.repeat_interval
+                {
+                    held.last_event = now;
+                    self.push(KeyEvent::Repeated(code));
+                }
+                return;
+            }
+        }
+        self.release();
+        self.held = Some(Held {
+            code,
+            toggle,
+            pressed_at: now,
+            last_frame: now,
+            last_event: now,
+            long_press: false,
+        });
+        self.push(KeyEvent::Pressed(code));
+    }
+
+    /// Report a received RC5 frame, the command includes the RC5X bit
+    pub fn rc5(&mut self, now: u32, frame: &Rc5Frame) {
+        let code = KeyCode {
+            address: frame.address as u16,
+            command: frame.extended_command(),
+        };
+        self.frame(now, code, frame.toggle);
+    }
+
+    /// Report a received RC6 frame
+    pub fn rc6(&mut self, now: u32, frame: &Rc6Frame) {
+        let code = KeyCode {
+            address: frame.address as u16,
+            command: frame.command,
+        };
+        self.frame(now, code, frame.toggle);
+    }
+
+    /// Report a received SIRC frame, the extended field is the address high byte
+    ///
+    /// SIRC has no toggle bit, presses of the same key are only told apart
+    /// if they are more than `release_after` ms apart.
+    pub fn sirc(&mut self, now: u32, frame: &SircFrame) {
+        let code = KeyCode {
+            address: u16::from_le_bytes([frame.address, frame.extended]),
+            command: frame.command,
+        };
+        self.frame(now, code, false);
+    }
+
+    /// Report a received NEC code
+    ///
+    /// NEC has no toggle bit, every frame is a new press and the repeat
+    /// codes keep the key held.
+    pub fn nec(&mut self, now: u32, code: &NecCode) {
+        let (frame, repeat) = match *code {
+            NecCode::Frame(frame) => (frame, false),
+            NecCode::Repeat(frame) => (frame, true),
+        };
+        let code = KeyCode {
+            address: frame.address,
+            command: frame.command,
+        };
+        let toggle = match self.held.as_ref() {
+            Some(held) if held.code == code && repeat => held.toggle,
+            Some(held) if held.code == code => !held.toggle,
+            _ => false,
+        };
+        self.frame(now, code, toggle);
+    }
+
+    /// Report a received frame of any protocol
+    pub fn ir(&mut self, now: u32, frame: &IrFrame) {
+        match frame {
+            IrFrame::Rc5(frame) => self.rc5(now, frame),
+            IrFrame::Rc6(frame) => self.rc6(now, frame),
+            IrFrame::Nec(code) => self.nec(now, code),
+            IrFrame::Sirc(frame) => self.sirc(now, frame),
+        }
+    }
+
+    /// Next key event, call it periodically to detect long presses and releases
+    pub fn poll(&mut self, now: u32) -> Option<KeyEvent> {
+        self.expire(now);
+        if let Some(held) = self.held.as_mut() {
+            if self.long_press_after > 0
+                && !held.long_press
+                && now.wrapping_sub(held.pressed_at) >= self.long_press_after
+            {
+                held.long_press = true;
+                let code = held.code;
+                self.push(KeyEvent::LongPress(code));
+            }
+        }
+        let event = self.pending[0].take();
+        self.pending.swap(0, 1);
+        event
+    }
+
+    /// Release the held key if its frames stopped
+    fn expire(&mut self, now: u32) {
+        if let Some(held) = self.held.as_ref() {
+            if now.wrapping_sub(held.last_frame) > self.release_after {
+                self.release();
+            }
+        }
+    }
+
+    fn release(&mut self) {
+        if let Some(held) = self.held.take() {
+            self.push(KeyEvent::Released(held.code));
+        }
+    }
+
+    /// Queue an event, the oldest one is lost if the caller does not poll
+    fn push(&mut self, event: KeyEvent) {
+        if self.pending[0].is_none() {
+            self.pending[0] = Some(event);
+        } else {
+            if self.pending[1].is_some() {
+                self.pending[0] = self.pending[1];
+            }
+            self.pending[1] = Some(event);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use super::*;
+    use crate::infrared::nec::NecFrame;
+    use std::vec::Vec;
+
+    const POWER: KeyCode = KeyCode {
+        address: 0,
+        command: 12,
+    };
+    const VOLUME: KeyCode = KeyCode {
+        address: 0,
+        command: 16,
+    };
+
+    /// Frames every repeat period from start until frames_until, polled every ms
+    fn run(
+        keys: &mut KeyTracker,
+        code: KeyCode,
+        start: u32,
+        frames_until: u32,
+        until: u32,
+    ) -> Vec<(u32, KeyEvent)> {
+        let mut events = Vec::new();
+        for now in start..until {
+            if now < frames_until && (now - start) % REPEAT_PERIOD_MS == 0 {
+                keys.frame(now, code, false);
+            }
+            while let Some(event) = keys.poll(now) {
+                events.push((now, event));
+            }
+        }
+        events
+    }
+
+    #[test]
+    fn press_and_release() {
+        let mut keys = KeyTracker::new();
+        let events = run(&mut keys, POWER, 1000, 1400, 1600);
+        assert_eq!(events, [(1000, KeyEvent::Pressed(POWER))]);
+        assert_eq!(keys.held(), Some(POWER));
+        // last frame at 1342 is 285 ms old
+        let events = run(&mut keys, POWER, 1600, 1600, 1800);
+        assert_eq!(events, [(1628, KeyEvent::Released(POWER))]);
+        assert_eq!(keys.held(), None);
+    }
+
+    #[test]
+    fn repeat_and_long_press() {
+        let mut keys = KeyTracker::new();
+        let events = run(&mut keys, VOLUME, 0, 1500, 2000);
+        let repeats: Vec<u32> = events
+            .iter()
+            .filter(|(_, event)| *event == KeyEvent::Repeated(VOLUME))
+            .map(|(now, _)| *now)
+            .collect();
+        // frames every 114 ms, repeat events rate limited to 200 ms
+        assert_eq!(repeats, [570, 798, 1026, 1254, 1482]);
+        assert!(events.contains(&(1000, KeyEvent::LongPress(VOLUME))));
+        assert_eq!(events.last(), Some(&(1768, KeyEvent::Released(VOLUME))));
+        assert_eq!(events.len(), 8);
+    }
+
+    #[test]
+    fn toggle_bit_tells_new_press() {
+        let mut keys = KeyTracker::new();
+        let mut events = Vec::new();
+        for (now, code, toggle) in [
+            (0, POWER, false),
+            (114, POWER, false),
+            (228, POWER, true),
+            (342, VOLUME, true),
+        ] {
+            keys.frame(now, code, toggle);
+            while let Some(event) = keys.poll(now) {
+                events.push(event);
+            }
+        }
+        assert_eq!(
+            events,
+            [
+                KeyEvent::Pressed(POWER),
+                KeyEvent::Released(POWER),
+                KeyEvent::Pressed(POWER),
+                KeyEvent::Released(POWER),
+                KeyEvent::Pressed(VOLUME),
+            ]
+        );
+    }
+
+    #[test]
+    fn nec_frames_and_repeat_codes() {
+        let mut keys = KeyTracker::new();
+        let frame = NecFrame::new(0x00, 0x16);
+        let mut events = Vec::new();
+        for (now, code) in [
+            (0, NecCode::Frame(frame)),
+            (108, NecCode::Repeat(frame)),
+            (216, NecCode::Frame(frame)),
+            (324, NecCode::Repeat(frame)),
+        ] {
+            keys.nec(now, &code);
+            while let Some(event) = keys.poll(now) {
+                events.push(event);
+            }
+        }
+        let code = KeyCode {
+            address: 0x00,
+            command: 0x16,
+        };
+        assert_eq!(
+            events,
+            [
+                KeyEvent::Pressed(code),
+                KeyEvent::Released(code),
+                KeyEvent::Pressed(code),
+            ]
+        );
+        assert_eq!(keys.held(), Some(code));
+    }
+}
@@ -0,0 +1,368 @@
+//!
+//! NEC protocol with standard and extended addresses
+//!
+//! NEC uses pulse distance coding on a 38 kHz carrier. A frame starts with a
+//! 9 ms leader mark and a 4.5 ms space, followed by 32 bits sent LSB first:
+//!
+//! | address | inverted address or address high byte | command | inverted command |
+//!
+//! Each bit is a 562.5 µs mark followed by a 562.5 µs (0) or 1687.5 µs (1)
+//! space, a final mark terminates the frame. While a key is held, repeat
+//! codes (9 ms mark, 2.25 ms space, final mark) follow every 108 ms.
+//!
+//! The decoder works on the durations between the edges of the receiver
+//! output; marks are the periods the carrier is received, i.e. the output
+//! of an active low receiver is low.
+//!
+//! ```ignore
+//! let mut receiver = NecDecoder::new();
+//! match receiver.next(carrier, duration_us) {
+//!     Some(NecCode::Frame(frame)) => defmt::println!("{}", frame),
+//!     Some(NecCode::Repeat(frame)) => defmt::println!("repeat {}", frame),
+//!     None => (),
+//! }
+//! ```
+
+use super::{within, IrDecoder};
+
+/// Carrier frequency in Hz
+pub const CARRIER_HZ: u32 = 38_000;
+/// Period of repeat codes while a key is held in ms
+pub const REPEAT_PERIOD_MS: u32 = 108;
+/// All marks and spaces are multiples of this unit in µs
+pub const UNIT_US: u32 = 562;
+
+const LEADER_MARK_US: u32 = 9000;
+const LEADER_SPACE_US: u32 = 4500;
+const REPEAT_SPACE_US: u32 = 2250;
+const BIT_MARK_US: u32 = 562;
+const ZERO_SPACE_US: u32 = 562;
+const ONE_SPACE_US: u32 = 1687;
+const FRAME_BITS: u8 = 32;
+
+/// Content of a NEC frame
+#[derive(Clone, Copy, PartialEq, Debug, defmt::Format)]
+pub struct NecFrame {
+    /// Device address, 0..=255 unless extended
+    pub address: u16,
+    pub command: u8,
+    /// 16 bit address instead of the 8 bit address and its inverse
+    pub extended: bool,
+}
+
+impl NecFrame {
+    /// Frame with an 8 bit address
+    pub fn new(address: u8, command: u8) -> Self {
+        NecFrame {
+            address: address as u16,
+            command,
+            extended: false,
+        }
+    }
+
+    /// Frame with a 16 bit address
+    ///
+    /// Addresses whose high byte is the inverted low byte are received as
+    /// standard frames.
+    pub fn extended(address: u16, command: u8) -> Self {
+        NecFrame {
+            address,
+            command,
+            extended: true,
+        }
+    }
+
+    /// The 32 bits in transmission order, LSB first
+    fn bits(&self) -> u32 {
+        let low = self.address as u8;
+        let high = if self.extended {
+            (self.address >> 8) as u8
+        } else {
+            !low
+        };
+        u32::from_le_bytes([low, high, self.command, !self.command])
+    }
+
+    /// Frame of received bits, None if the command check fails
+    fn from_bits(bits: u32) -> Option<Self> {
+        let [low, high, command, inverted] = bits.to_le_bytes();
+        if command != !inverted {
+            None
+        } else if high == !low {
+            Some(NecFrame::new(low, command))
+        } else {
+            Some(NecFrame::extended(u16::from_le_bytes([low, high]), command))
+        }
+    }
+}
+
+/// Received code
+#[derive(Clone, Copy, PartialEq, Debug, defmt::Format)]
+pub enum NecCode {
+    Frame(NecFrame),
+    /// Repeat code, holds the frame received before
+    ///
+    /// Only reported if nothing but repeat codes followed the frame and no
+    /// space was longer than twice the repeat period.
+    Repeat(NecFrame),
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum State {
+    Idle,
+    Leader,
+    RepeatMark,
+    BitMark,
+    BitSpace,
+}
+
+pub struct NecDecoder {
+    state: State,
+    bits: u32,
+    count: u8,
+    last: Option<NecFrame>,
+}
+
+impl Default for NecDecoder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl NecDecoder {
+    pub const fn new() -> Self {
+        NecDecoder {
+            state: State::Idle,
+            bits: 0,
+            count: 0,
+            last: None,
+        }
+    }
+
+    /// Process the duration of a mark or space that just ended
+    ///
+    /// Args:
+    /// * carrier - true for a mark, false for a space
+    /// * duration_us - duration in µs
+    ///
+    /// Returns:
+    /// * Some(code) - a frame or repeat code is completely received
+    pub fn next(&mut self, carrier: bool, duration_us: u32) -> Option<NecCode> {
+        let mut code = None;
+        self.state = match (self.state, carrier) {
+            (_, true) if within(duration_us, LEADER_MARK_US) => State::Leader,
+            (State::Leader, false) if within(duration_us, LEADER_SPACE_US) => {
+                self.bits = 0;
+                self.count = 0;
+                State::BitMark
+            }
+            (State::Leader, false) if within(duration_us, REPEAT_SPACE_US) => State::RepeatMark,
+            (State::RepeatMark, true) if within(duration_us, BIT_MARK_US) => {
+                code = self.last.map(NecCode::Repeat);
+                State::Idle
+            }
+            (State::BitMark, true) if within(duration_us, BIT_MARK_US) => {
+                if self.count < FRAME_BITS {
+                    State::BitSpace
+                } else {
+                    self.last = NecFrame::from_bits(self.bits);
+                    code = self.last.map(NecCode::Frame);
+                    State::Idle
+                }
+            }
+            (State::BitSpace, false) if within(duration_us, ZERO_SPACE_US) => {
+                self.count += 1;
+                State::BitMark
+            }
+            (State::BitSpace, false) if within(duration_us, ONE_SPACE_US) => {
+                self.bits |= 1 << self.count;
+                self.count += 1;
+                State::BitMark
+            }
+            // the pause between a frame and its repeat codes
+            (State::Idle, false) if duration_us <= 2 * REPEAT_PERIOD_MS * 1000 => State::Idle,
+            _ => {
+                // a stray repeat code must not repeat an old frame
+                self.last = None;
+                State::Idle
+            }
+        };
+        code
+    }
+}
+
+impl IrDecoder for NecDecoder {
+    type Frame = NecCode;
+
+    fn next(&mut self, carrier: bool, duration_us: u32) -> Option<NecCode> {
+        NecDecoder::next(self, carrier, duration_us)
+    }
+
+    fn reset(&mut self) {
+        self.state = State::Idle;
+        self.last = None;
+    }
+}
+
+/// Marks and spaces of a frame or repeat code as (carrier, duration in µs)
+pub struct NecEncoder {
+    bits: u32,
+    step: u8,
+    repeat: bool,
+}
+
+impl NecEncoder {
+    pub fn new(frame: NecFrame) -> Self {
+        NecEncoder {
+            bits: frame.bits(),
+            step: 0,
+            repeat: false,
+        }
+    }
+
+    /// Repeat code, to be sent every 108 ms after a frame while a key is held
+    pub fn repeat() -> Self {
+        NecEncoder {
+            bits: 0,
+            step: 0,
+            repeat: true,
+        }
+    }
+}
+
+impl Iterator for NecEncoder {
+    type Item = (bool, u32);
+
+    fn next(&mut self) -> Option<(bool, u32)> {
+        let last_step = if self.repeat { 2 } else { 2 + 2 * FRAME_BITS };
+        let pulse = match self.step {
+            0 => (true, LEADER_MARK_US),
+            1 if self.repeat => (false, REPEAT_SPACE_US),
+            1 => (false, LEADER_SPACE_US),
+            step if step > last_step => return None,
+            step if step % 2 == 0 => (true, BIT_MARK_US),
+            step => {
+                if self.bits & (1 << ((step - 3) / 2)) != 0 {
+                    (false, ONE_SPACE_US)
+                } else {
+                    (false, ZERO_SPACE_US)
+                }
+            }
+        };
+        self.step += 1;
+        Some(pulse)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use super::*;
+    use std::vec::Vec;
+
+    fn receive(
+        decoder: &mut NecDecoder,
+        pulses: impl Iterator<Item = (bool, u32)>,
+    ) -> Vec<NecCode> {
+        pulses
+            .filter_map(|(carrier, duration)| decoder.next(carrier, duration))
+            .collect()
+    }
+
+    #[test]
+    fn encode_and_decode() {
+        let mut decoder = NecDecoder::new();
+        let frame = NecFrame::new(0x04, 0x08);
+        let pulses: Vec<(bool, u32)> = NecEncoder::new(frame).collect();
+        assert_eq!(pulses.len(), 67);
+        assert_eq!(pulses[..3], [(true, 9000), (false, 4500), (true, 562)]);
+        // address 0x04 LSB first: 0, 0, 1
+        assert_eq!(pulses[3], (false, 562));
+        assert_eq!(pulses[7], (false, 1687));
+        assert_eq!(
+            receive(&mut decoder, pulses.into_iter()),
+            [NecCode::Frame(frame)]
+        );
+
+        let frame = NecFrame::extended(0x1234, 0xff);
+        assert_eq!(
+            receive(&mut decoder, NecEncoder::new(frame)),
+            [NecCode::Frame(frame)]
+        );
+        let standard = NecFrame::extended(0xfe01, 0x00);
+        assert_eq!(
+            receive(&mut decoder, NecEncoder::new(standard)),
+            [NecCode::Frame(NecFrame::new(0x01, 0x00))]
+        );
+    }
+
+    #[test]
+    fn repeat_codes() {
+        let mut decoder = NecDecoder::new();
+        assert_eq!(NecEncoder::repeat().count(), 3);
+        // no frame to repeat
+        assert!(receive(&mut decoder, NecEncoder::repeat()).is_empty());
+        let frame = NecFrame::new(0x00, 0x45);
+        let pulses = NecEncoder::new(frame)
+            .chain(core::iter::once((false, 40_000)))
+            .chain(NecEncoder::repeat())
+            .chain(core::iter::once((false, 96_000)))
+            .chain(NecEncoder::repeat());
+        assert_eq!(
+            receive(&mut decoder, pulses),
+            [
+                NecCode::Frame(frame),
+                NecCode::Repeat(frame),
+                NecCode::Repeat(frame)
+            ]
+        );
+    }
+
+    #[test]
+    fn stray_repeat_codes() {
+        let mut decoder = NecDecoder::new();
+        let frame = NecFrame::new(0x00, 0x45);
+        // long after the frame
+        let pulses = NecEncoder::new(frame)
+            .chain(core::iter::once((false, 500_000)))
+            .chain(NecEncoder::repeat());
+        assert_eq!(receive(&mut decoder, pulses), [NecCode::Frame(frame)]);
+        // after pulses not belonging to a frame, e.g. of another remote
+        let pulses = NecEncoder::new(frame)
+            .chain([(false, 40_000), (true, 889), (false, 889)])
+            .chain(NecEncoder::repeat());
+        assert_eq!(receive(&mut decoder, pulses), [NecCode::Frame(frame)]);
+    }
+
+    #[test]
+    fn tolerate_receiver_distortion() {
+        let mut decoder = NecDecoder::new();
+        let frame = NecFrame::new(0x80, 0x01);
+        // receivers stretch marks and shorten spaces
+        let pulses = NecEncoder::new(frame).map(|(carrier, duration)| {
+            if carrier {
+                (carrier, duration + 120)
+            } else {
+                (carrier, duration - 120)
+            }
+        });
+        assert_eq!(receive(&mut decoder, pulses), [NecCode::Frame(frame)]);
+    }
+
+    #[test]
+    fn reject_corrupted_frames() {
+        let mut decoder = NecDecoder::new();
+        let frame = NecFrame::new(0x10, 0x20);
+        // flipped command bit fails the check with the inverted command
+        let mut pulses: Vec<(bool, u32)> = NecEncoder::new(frame).collect();
+        pulses[3 + 2 * 16] = (false, ONE_SPACE_US);
+        assert!(receive(&mut decoder, pulses.into_iter()).is_empty());
+        // glitch within a frame
+        let mut pulses: Vec<(bool, u32)> = NecEncoder::new(frame).collect();
+        pulses[20] = (pulses[20].0, 100);
+        assert!(receive(&mut decoder, pulses.into_iter()).is_empty());
+        // no repeat of the rejected frames
+        assert!(receive(&mut decoder, NecEncoder::repeat()).is_empty());
+    }
+}
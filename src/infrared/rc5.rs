@@ -0,0 +1,397 @@
+//!
+//! Philips RC5 and RC5X frames
+//!
+//! A frame consists of 14 bits, transmitted MSB first:
+//!
+//! | start | field | toggle | address (5 bit) | command (6 bit) |
+//!
+//! The start bit is always 1. RC5X reuses the field bit as inverted
+//! seventh command bit, i.e. commands 0..=63 have the field bit set and
+//! commands 64..=127 have it cleared. The toggle bit changes with every
+//! key press and stays while a key is held.
+//!
+//! Frames are either decoded from periodic samples by the Manchester
+//! `decoder` or from the durations between edges by `Rc5Decoder`.
+//!
+//! ```ignore
+//! let mut receiver = rc5::decoder();
+//! if let Some(datagram) = receiver.next(infrared.is_high().unwrap()) {
+//!     if let Ok(frame) = Rc5Frame::from_datagram(&datagram) {
+//!         defmt::println!("{}", frame);
+//!     }
+//! }
+//! ```
+
+use manchester_code::{ActivityLevel, BitOrder, Datagram, Decoder, SyncOnTurningEdge};
+
+use super::{units, IrDecoder};
+
+/// Carrier frequency in Hz
+pub const CARRIER_HZ: u32 = 36_000;
+/// Duration of half a bit in µs (32 cycles of the carrier)
+pub const HALF_BIT_US: u32 = 889;
+/// Period of repeated frames while a key is held in ms
+pub const REPEAT_PERIOD_MS: u32 = 114;
+/// Number of bits of a frame including the start bit
+pub const FRAME_BITS: u8 = 14;
+
+const START_BIT: u8 = 13;
+const FIELD_BIT: u8 = 12;
+const TOGGLE_BIT: u8 = 11;
+const ADDRESS_BITS: (u8, u8) = (6, 11);
+const COMMAND_BITS: (u8, u8) = (0, 6);
+
+/// Decoder for an active low infrared receiver like the TSOP38238
+///
+/// The receiver must be sampled three times per half bit, i.e. every 296 µs.
+/// Synchronizing on the first edge keeps the start bit in the datagram.
+pub const fn decoder() -> Decoder {
+    Decoder::new(
+        ActivityLevel::Low,
+        SyncOnTurningEdge::First,
+        BitOrder::BigEndian,
+    )
+}
+
+/// Reasons to reject a received datagram
+#[derive(Clone, Copy, PartialEq, Debug, defmt::Format)]
+pub enum Rc5Error {
+    /// Datagram does not have 14 bits, holds the received length
+    Length(u8),
+    /// Start bit is not set
+    StartBit,
+}
+
+/// Content of a RC5 frame
+#[derive(Clone, Copy, PartialEq, Debug, defmt::Format)]
+pub struct Rc5Frame {
+    /// Set for commands 0..=63, cleared for the RC5X commands 64..=127
+    pub field_bit: bool,
+    pub toggle: bool,
+    /// Device address 0..=31
+    pub address: u8,
+    /// Lower six bits of the command 0..=63
+    pub command: u8,
+}
+
+impl Rc5Frame {
+    /// Frame for address 0..=31 and the (extended) command 0..=127
+    pub fn new(address: u8, command: u8, toggle: bool) -> Self {
+        if address > 31 {
+            panic!("RC5 address out of range");
+        }
+        if command > 127 {
+            panic!("RC5X command out of range");
+        }
+        Rc5Frame {
+            field_bit: command < 64,
+            toggle,
+            address,
+            command: command & 0x3f,
+        }
+    }
+
+    /// Seven bit command, including the inverted field bit of RC5X
+    pub fn extended_command(&self) -> u8 {
+        if self.field_bit {
+            self.command
+        } else {
+            self.command | 0x40
+        }
+    }
+
+    /// Interpret a received datagram as frame
+    pub fn from_datagram(datagram: &Datagram) -> Result<Self, Rc5Error> {
+        if datagram.len() != FRAME_BITS {
+            return Err(Rc5Error::Length(datagram.len()));
+        }
+        Self::from_bits(datagram.extract_data(0, FRAME_BITS) as u16)
+    }
+
+    /// Datagram to be sent by a `manchester_code::Encoder`, MSB first
+    pub fn to_datagram(&self) -> Datagram {
+        let bits = self.bits();
+        let mut repr = [b'0'; FRAME_BITS as usize];
+        for (index, bit) in repr.iter_mut().enumerate() {
+            if bits & (1 << (START_BIT as usize - index)) != 0 {
+                *bit = b'1';
+            }
+        }
+        Datagram::new(core::str::from_utf8(&repr).unwrap())
+    }
+
+    /// The 14 bits of the frame, the start bit is the MSB
+    fn bits(&self) -> u16 {
+        (1 << START_BIT)
+            | (self.field_bit as u16) << FIELD_BIT
+            | (self.toggle as u16) << TOGGLE_BIT
+            | ((self.address & 0x1f) as u16) << ADDRESS_BITS.0
+            | (self.command & 0x3f) as u16
+    }
+
+    fn from_bits(bits: u16) -> Result<Self, Rc5Error> {
+        let field = |(low, high): (u8, u8)| ((bits >> low) & ((1 << (high - low)) - 1)) as u8;
+        if bits & (1 << START_BIT) == 0 {
+            return Err(Rc5Error::StartBit);
+        }
+        Ok(Rc5Frame {
+            field_bit: bits & (1 << FIELD_BIT) != 0,
+            toggle: bits & (1 << TOGGLE_BIT) != 0,
+            address: field(ADDRESS_BITS),
+            command: field(COMMAND_BITS),
+        })
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum State {
+    Idle,
+    Units,
+}
+
+/// Decoder working on the durations between edges
+///
+/// The first half of the start bit is a space and not seen, adjacent
+/// halves of the same level merge. The durations are quantized into units
+/// of half a bit to restore the halves.
+pub struct Rc5Decoder {
+    state: State,
+    /// Levels of the halves, bit 0 is the first half, 1 is a mark
+    units: u32,
+    count: u8,
+}
+
+impl Default for Rc5Decoder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Rc5Decoder {
+    pub const fn new() -> Self {
+        Rc5Decoder {
+            state: State::Idle,
+            units: 0,
+            count: 0,
+        }
+    }
+
+    /// Process the duration of a mark or space that just ended
+    ///
+    /// Args:
+    /// * carrier - true for a mark, false for a space
+    /// * duration_us - duration in µs
+    ///
+    /// Returns:
+    /// * Some(frame) - a frame is completely received
+    pub fn next(&mut self, carrier: bool, duration_us: u32) -> Option<Rc5Frame> {
+        let units = units(duration_us, HALF_BIT_US);
+        let mut frame = None;
+        self.state = match (self.state, carrier) {
+            (State::Idle, true) if (1..=2).contains(&units) => {
+                self.units = ((1 << units) - 1) << 1;
+                self.count = 1 + units as u8;
+                State::Units
+            }
+            (State::Units, _) if (1..=2).contains(&units) => {
+                if carrier {
+                    self.units |= ((1 << units) - 1) << self.count;
+                }
+                self.count += units as u8;
+                // the space of a final 0 merges with the pause after the frame
+                if carrier && self.count >= 2 * FRAME_BITS - 1 {
+                    if self.count <= 2 * FRAME_BITS {
+                        frame = self.frame();
+                    }
+                    State::Idle
+                } else if self.count >= 2 * FRAME_BITS {
+                    State::Idle
+                } else {
+                    State::Units
+                }
+            }
+            _ => State::Idle,
+        };
+        frame
+    }
+
+    /// Frame of the received halves, a 1 is a space followed by a mark
+    fn frame(&self) -> Option<Rc5Frame> {
+        let mut bits = 0u16;
+        for index in 0..FRAME_BITS {
+            let first = self.units & (1 << (2 * index)) != 0;
+            let second = self.units & (1 << (2 * index + 1)) != 0;
+            if first == second {
+                return None;
+            }
+            bits = bits << 1 | second as u16;
+        }
+        Rc5Frame::from_bits(bits).ok()
+    }
+}
+
+impl IrDecoder for Rc5Decoder {
+    type Frame = Rc5Frame;
+
+    fn next(&mut self, carrier: bool, duration_us: u32) -> Option<Rc5Frame> {
+        Rc5Decoder::next(self, carrier, duration_us)
+    }
+
+    fn reset(&mut self) {
+        self.state = State::Idle;
+    }
+}
+
+/// Marks and spaces of a frame as (carrier, duration in µs)
+///
+/// The leading space of the start bit is part of the pause before the
+/// frame, the frame ends with a mark.
+pub struct Rc5Encoder {
+    /// Levels of the halves, bit 0 is sent first, 1 is a mark
+    units: u32,
+    unit: u8,
+    end: u8,
+}
+
+impl Rc5Encoder {
+    pub fn new(frame: Rc5Frame) -> Self {
+        let bits = frame.bits();
+        let mut units = 0u32;
+        for index in 0..FRAME_BITS {
+            // a 1 is a space followed by a mark
+            let bit = bits & (1 << (START_BIT - index)) != 0;
+            let mark_unit = if bit { 2 * index + 1 } else { 2 * index };
+            units |= 1 << mark_unit;
+        }
+        Rc5Encoder {
+            units,
+            unit: 1,
+            end: 32 - units.leading_zeros() as u8,
+        }
+    }
+}
+
+impl Iterator for Rc5Encoder {
+    type Item = (bool, u32);
+
+    fn next(&mut self) -> Option<(bool, u32)> {
+        if self.unit >= self.end {
+            return None;
+        }
+        let units = self.units;
+        let level = |unit: u8| units & (1 << unit) != 0;
+        let carrier = level(self.unit);
+        let start = self.unit;
+        while self.unit < self.end && level(self.unit) == carrier {
+            self.unit += 1;
+        }
+        Some((carrier, (self.unit - start) as u32 * HALF_BIT_US))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use super::*;
+    use manchester_code::{DatagramBigEndianIterator, Encoder};
+
+    #[test]
+    fn decode_and_encode() {
+        let datagram = Datagram::new("11_0_10101_001101");
+        let frame = Rc5Frame::from_datagram(&datagram).unwrap();
+        assert_eq!(frame, Rc5Frame::new(0x15, 0x0d, false));
+        assert_eq!(frame.to_datagram(), datagram);
+
+        let frame = Rc5Frame::new(0x1f, 0x7f, true);
+        assert!(!frame.field_bit);
+        assert_eq!(frame.command, 0x3f);
+        assert_eq!(frame.extended_command(), 0x7f);
+        assert_eq!(frame.to_datagram(), Datagram::new("10_1_11111_111111"));
+        assert_eq!(Rc5Frame::from_datagram(&frame.to_datagram()), Ok(frame));
+    }
+
+    #[test]
+    fn reject_malformed_datagrams() {
+        let short = Datagram::new("11_0_10101_00110");
+        assert_eq!(Rc5Frame::from_datagram(&short), Err(Rc5Error::Length(13)));
+        let long = Datagram::new("11_0_10101_0011010");
+        assert_eq!(Rc5Frame::from_datagram(&long), Err(Rc5Error::Length(15)));
+        let no_start = Datagram::new("01_0_10101_001101");
+        assert_eq!(Rc5Frame::from_datagram(&no_start), Err(Rc5Error::StartBit));
+    }
+
+    #[test]
+    fn receive_sampled_frame() {
+        let frame = Rc5Frame::new(0x05, 0x42, true);
+        let mut receiver = decoder();
+        let encoder = Encoder::<DatagramBigEndianIterator>::new(frame.to_datagram());
+        // the receiver output is low while the carrier is on
+        let samples = core::iter::repeat(true)
+            .take(12)
+            .chain(encoder.flat_map(|carrier| core::iter::repeat(!carrier).take(3)))
+            .chain(core::iter::repeat(true).take(12));
+        let received: Option<Datagram> = samples.filter_map(|sample| receiver.next(sample)).last();
+        assert_eq!(Rc5Frame::from_datagram(&received.unwrap()), Ok(frame));
+    }
+
+    #[test]
+    fn receive_edge_durations() {
+        let pulses: std::vec::Vec<(bool, u32)> =
+            Rc5Encoder::new(Rc5Frame::new(0x00, 0x01, true)).collect();
+        // start bit, field bit 1, toggle 1, address 00000, command 000001
+        assert_eq!(
+            pulses,
+            [
+                (true, 889),
+                (false, 889),
+                (true, 889),
+                (false, 889),
+                (true, 1778),
+                (false, 889),
+                (true, 889),
+                (false, 889),
+                (true, 889),
+                (false, 889),
+                (true, 889),
+                (false, 889),
+                (true, 889),
+                (false, 889),
+                (true, 889),
+                (false, 889),
+                (true, 889),
+                (false, 889),
+                (true, 889),
+                (false, 889),
+                (true, 889),
+                (false, 889),
+                (true, 889),
+                (false, 1778),
+                (true, 889),
+            ]
+        );
+
+        let mut receiver = Rc5Decoder::new();
+        for frame in [
+            Rc5Frame::new(0x05, 0x42, true),
+            Rc5Frame::new(0x00, 0x01, false),
+            Rc5Frame::new(0x1f, 0x3f, false),
+        ] {
+            let mut pulses: std::vec::Vec<(bool, u32)> = Rc5Encoder::new(frame).collect();
+            pulses.push((false, 50_000));
+            // receivers stretch marks
+            let received: std::vec::Vec<Rc5Frame> = pulses
+                .into_iter()
+                .filter_map(|(carrier, duration)| {
+                    let duration = if carrier {
+                        duration + 100
+                    } else {
+                        duration - 100
+                    };
+                    receiver.next(carrier, duration)
+                })
+                .collect();
+            assert_eq!(received, [frame]);
+        }
+    }
+}
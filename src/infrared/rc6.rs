@@ -0,0 +1,307 @@
+//!
+//! Philips RC6 mode 0
+//!
+//! A frame starts with a 2.666 ms leader mark and a 889 µs space, followed
+//! by Manchester coded bits on a 36 kHz carrier, MSB first:
+//!
+//! | start | mode (3 bit) | trailer | address (8 bit) | command (8 bit) |
+//!
+//! Unlike RC5 a 1 is a mark followed by a space. Half a bit lasts 444 µs,
+//! except for the trailer bit, which lasts twice as long and holds the
+//! toggle bit. The start bit is always 1, the mode is 0.
+//!
+//! Adjacent halves of the same level merge, so the decoder quantizes the
+//! durations between edges into 444 µs units and restores the halves.
+//!
+//! ```ignore
+//! let mut receiver = Rc6Decoder::new();
+//! if let Some(frame) = receiver.next(carrier, duration_us) {
+//!     defmt::println!("{}", frame);
+//! }
+//! ```
+
+use super::{units, IrDecoder};
+
+/// Carrier frequency in Hz
+pub const CARRIER_HZ: u32 = 36_000;
+/// Duration of half a bit in µs (16 cycles of the carrier)
+pub const HALF_BIT_US: u32 = 444;
+/// Period of repeated frames while a key is held in ms
+pub const REPEAT_PERIOD_MS: u32 = 107;
+
+const LEADER_MARK_UNITS: u32 = 6;
+const LEADER_SPACE_UNITS: u32 = 2;
+/// Units of half bit duration after the leader
+const FRAME_UNITS: u8 = 44;
+/// First unit of the mode, trailer and data bits
+const MODE_UNIT: u8 = 2;
+const TRAILER_UNIT: u8 = 8;
+const DATA_UNIT: u8 = 12;
+const MODE: u8 = 0;
+
+/// Content of a RC6 mode 0 frame
+#[derive(Clone, Copy, PartialEq, Debug, defmt::Format)]
+pub struct Rc6Frame {
+    pub toggle: bool,
+    pub address: u8,
+    pub command: u8,
+}
+
+impl Rc6Frame {
+    pub fn new(address: u8, command: u8, toggle: bool) -> Self {
+        Rc6Frame {
+            toggle,
+            address,
+            command,
+        }
+    }
+
+    /// Levels of the units after the leader, bit 0 is the first unit
+    fn units(&self) -> u64 {
+        let mut units = 0u64;
+        let mut bit = |unit: u8, width: u8, value: bool| {
+            // a 1 is a mark followed by a space
+            let mark_unit = if value { unit } else { unit + width };
+            units |= ((1 << width) - 1) << mark_unit;
+        };
+        bit(0, 1, true);
+        for index in 0..3 {
+            bit(MODE_UNIT + 2 * index, 1, (MODE >> (2 - index)) & 1 != 0);
+        }
+        bit(TRAILER_UNIT, 2, self.toggle);
+        let data = u16::from_be_bytes([self.address, self.command]);
+        for index in 0..16 {
+            bit(DATA_UNIT + 2 * index, 1, data & (0x8000 >> index) != 0);
+        }
+        units
+    }
+
+    /// Frame of the received units, None if the Manchester coding is
+    /// violated or the start bit or mode do not match
+    fn from_units(units: u64) -> Option<Self> {
+        let level = |unit: u8| units & (1 << unit) != 0;
+        let bit = |unit: u8, width: u8| {
+            let first = level(unit);
+            let valid = (1..width).all(|offset| level(unit + offset) == first)
+                && (width..2 * width).all(|offset| level(unit + offset) != first);
+            if valid {
+                Some(first)
+            } else {
+                None
+            }
+        };
+        let mut mode = 0;
+        for index in 0..3 {
+            mode = mode << 1 | bit(MODE_UNIT + 2 * index, 1)? as u8;
+        }
+        let mut data = 0u16;
+        for index in 0..16 {
+            data = data << 1 | bit(DATA_UNIT + 2 * index, 1)? as u16;
+        }
+        if !bit(0, 1)? || mode != MODE {
+            return None;
+        }
+        let [address, command] = data.to_be_bytes();
+        Some(Rc6Frame::new(address, command, bit(TRAILER_UNIT, 2)?))
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum State {
+    Idle,
+    Leader,
+    Units,
+}
+
+pub struct Rc6Decoder {
+    state: State,
+    units: u64,
+    count: u8,
+}
+
+impl Default for Rc6Decoder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Rc6Decoder {
+    pub const fn new() -> Self {
+        Rc6Decoder {
+            state: State::Idle,
+            units: 0,
+            count: 0,
+        }
+    }
+
+    /// Process the duration of a mark or space that just ended
+    ///
+    /// Args:
+    /// * carrier - true for a mark, false for a space
+    /// * duration_us - duration in µs
+    ///
+    /// Returns:
+    /// * Some(frame) - a frame is completely received
+    pub fn next(&mut self, carrier: bool, duration_us: u32) -> Option<Rc6Frame> {
+        let units = units(duration_us, HALF_BIT_US);
+        let mut frame = None;
+        self.state = match (self.state, carrier) {
+            (_, true) if units == LEADER_MARK_UNITS => State::Leader,
+            (State::Leader, false) if units == LEADER_SPACE_UNITS => {
+                self.units = 0;
+                self.count = 0;
+                State::Units
+            }
+            (State::Units, _) if (1..=3).contains(&units) => {
+                if carrier {
+                    self.units |= ((1 << units) - 1) << self.count;
+                }
+                self.count += units as u8;
+                // the space of a final 1 merges with the pause after the frame
+                if carrier && self.count >= FRAME_UNITS - 1 {
+                    if self.count <= FRAME_UNITS {
+                        frame = Rc6Frame::from_units(self.units);
+                    }
+                    State::Idle
+                } else if self.count >= FRAME_UNITS {
+                    State::Idle
+                } else {
+                    State::Units
+                }
+            }
+            _ => State::Idle,
+        };
+        frame
+    }
+}
+
+impl IrDecoder for Rc6Decoder {
+    type Frame = Rc6Frame;
+
+    fn next(&mut self, carrier: bool, duration_us: u32) -> Option<Rc6Frame> {
+        Rc6Decoder::next(self, carrier, duration_us)
+    }
+
+    fn reset(&mut self) {
+        self.state = State::Idle;
+    }
+}
+
+/// Marks and spaces of a frame as (carrier, duration in µs)
+///
+/// The frame ends with a mark, a pause of at least 2.666 ms must follow.
+pub struct Rc6Encoder {
+    /// Levels of the leader and frame units, bit 0 is sent first
+    units: u64,
+    unit: u8,
+    end: u8,
+}
+
+impl Rc6Encoder {
+    pub fn new(frame: Rc6Frame) -> Self {
+        let units = frame.units() << (LEADER_MARK_UNITS + LEADER_SPACE_UNITS)
+            | ((1 << LEADER_MARK_UNITS) - 1);
+        Rc6Encoder {
+            units,
+            unit: 0,
+            // the last unit is a space if the last bit is a 1
+            end: 64 - units.leading_zeros() as u8,
+        }
+    }
+}
+
+impl Iterator for Rc6Encoder {
+    type Item = (bool, u32);
+
+    fn next(&mut self) -> Option<(bool, u32)> {
+        if self.unit >= self.end {
+            return None;
+        }
+        let units = self.units;
+        let level = |unit: u8| units & (1 << unit) != 0;
+        let carrier = level(self.unit);
+        let start = self.unit;
+        while self.unit < self.end && level(self.unit) == carrier {
+            self.unit += 1;
+        }
+        Some((carrier, (self.unit - start) as u32 * HALF_BIT_US))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use super::*;
+    use std::vec::Vec;
+
+    fn receive(pulses: impl Iterator<Item = (bool, u32)>) -> Vec<Rc6Frame> {
+        let mut decoder = Rc6Decoder::new();
+        pulses
+            .filter_map(|(carrier, duration)| decoder.next(carrier, duration))
+            .collect()
+    }
+
+    #[test]
+    fn encode_and_decode() {
+        let frame = Rc6Frame::new(0x00, 0x0c, false);
+        let pulses: Vec<(bool, u32)> = Rc6Encoder::new(frame).collect();
+        // leader, start bit, mode 000, trailer 0 (space, mark), address bit 7 = 0
+        assert_eq!(
+            pulses[..12],
+            [
+                (true, 2664),
+                (false, 888),
+                (true, 444),
+                (false, 888),
+                (true, 444),
+                (false, 444),
+                (true, 444),
+                (false, 444),
+                (true, 444),
+                (false, 888),
+                (true, 888),
+                (false, 444),
+            ]
+        );
+        assert_eq!(pulses.last(), Some(&(true, 444)));
+        assert_eq!(receive(pulses.into_iter()), [frame]);
+
+        for frame in [
+            Rc6Frame::new(0xff, 0xff, true),
+            Rc6Frame::new(0x80, 0x01, true),
+            Rc6Frame::new(0x55, 0xaa, false),
+        ] {
+            let pulses = Rc6Encoder::new(frame).chain(core::iter::once((false, 2666)));
+            assert_eq!(receive(pulses), [frame]);
+        }
+    }
+
+    #[test]
+    fn tolerate_receiver_distortion() {
+        let frame = Rc6Frame::new(0x12, 0x34, true);
+        let pulses = Rc6Encoder::new(frame).map(|(carrier, duration)| {
+            if carrier {
+                (carrier, duration + 150)
+            } else {
+                (carrier, duration - 150)
+            }
+        });
+        assert_eq!(receive(pulses), [frame]);
+    }
+
+    #[test]
+    fn reject_other_modes_and_corrupted_frames() {
+        let frame = Rc6Frame::new(0x12, 0x34, false);
+        // mode 6 (110) as sent by Microsoft MCE remotes
+        let units = frame.units() ^ (0b1111 << MODE_UNIT);
+        assert_eq!(Rc6Frame::from_units(units), None);
+        // trailer of single width
+        let units = frame.units() ^ (0b0110 << TRAILER_UNIT);
+        assert_eq!(Rc6Frame::from_units(units), None);
+        // a pulse too long within the frame
+        let mut pulses: Vec<(bool, u32)> = Rc6Encoder::new(frame).collect();
+        pulses[10].1 += 1000;
+        assert!(receive(pulses.into_iter()).is_empty());
+    }
+}
@@ -0,0 +1,288 @@
+//!
+//! Receiver of all supported protocols
+//!
+//! `IrReceiver` feeds every mark and space to the decoders of the enabled
+//! protocols in parallel and reports the first frame with its protocol.
+//! Since the protocols differ in their leaders and timing, a frame matches
+//! only one of them. Should two decoders still complete on the same pulse,
+//! the protocol listed first in `Protocol::ALL` wins and the other frame is
+//! dropped.
+//!
+//! ```ignore
+//! let mut receiver = IrReceiver::new().with_protocol(Protocol::Sirc, false);
+//! if let Some(frame) = receiver.next(carrier, duration_us) {
+//!     defmt::println!("{}: {}", frame.protocol(), frame);
+//! }
+//! ```
+
+use super::nec::{self, NecCode, NecDecoder};
+use super::rc5::{self, Rc5Decoder, Rc5Frame};
+use super::rc6::{self, Rc6Decoder, Rc6Frame};
+use super::sirc::{self, SircDecoder, SircFrame};
+use super::IrDecoder;
+
+#[derive(Clone, Copy, PartialEq, Debug, defmt::Format)]
+pub enum Protocol {
+    Rc5,
+    Rc6,
+    Nec,
+    Sirc,
+}
+
+impl Protocol {
+    pub const ALL: [Protocol; 4] = [Protocol::Rc5, Protocol::Rc6, Protocol::Nec, Protocol::Sirc];
+
+    /// Carrier frequency in Hz
+    pub fn carrier_hz(self) -> u32 {
+        match self {
+            Protocol::Rc5 => rc5::CARRIER_HZ,
+            Protocol::Rc6 => rc6::CARRIER_HZ,
+            Protocol::Nec => nec::CARRIER_HZ,
+            Protocol::Sirc => sirc::CARRIER_HZ,
+        }
+    }
+
+    /// Period of repeated frames while a key is held in ms
+    pub fn repeat_period_ms(self) -> u32 {
+        match self {
+            Protocol::Rc5 => rc5::REPEAT_PERIOD_MS,
+            Protocol::Rc6 => rc6::REPEAT_PERIOD_MS,
+            Protocol::Nec => nec::REPEAT_PERIOD_MS,
+            Protocol::Sirc => sirc::REPEAT_PERIOD_MS,
+        }
+    }
+
+    /// Common unit of all marks and spaces in µs
+    pub fn unit_us(self) -> u32 {
+        match self {
+            Protocol::Rc5 => rc5::HALF_BIT_US,
+            Protocol::Rc6 => rc6::HALF_BIT_US,
+            Protocol::Nec => nec::UNIT_US,
+            Protocol::Sirc => sirc::UNIT_US,
+        }
+    }
+
+    fn mask(self) -> u8 {
+        1 << self as u8
+    }
+}
+
+/// Frame of any protocol
+#[derive(Clone, Copy, PartialEq, Debug, defmt::Format)]
+pub enum IrFrame {
+    Rc5(Rc5Frame),
+    Rc6(Rc6Frame),
+    Nec(NecCode),
+    Sirc(SircFrame),
+}
+
+impl IrFrame {
+    /// Protocol that matched
+    pub fn protocol(&self) -> Protocol {
+        match self {
+            IrFrame::Rc5(_) => Protocol::Rc5,
+            IrFrame::Rc6(_) => Protocol::Rc6,
+            IrFrame::Nec(_) => Protocol::Nec,
+            IrFrame::Sirc(_) => Protocol::Sirc,
+        }
+    }
+}
+
+pub struct IrReceiver {
+    enabled: u8,
+    rc5: Rc5Decoder,
+    rc6: Rc6Decoder,
+    nec: NecDecoder,
+    sirc: SircDecoder,
+}
+
+impl Default for IrReceiver {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl IrReceiver {
+    /// Receiver with all protocols enabled
+    pub const fn new() -> Self {
+        IrReceiver {
+            enabled: 0x0f,
+            rc5: Rc5Decoder::new(),
+            rc6: Rc6Decoder::new(),
+            nec: NecDecoder::new(),
+            sirc: SircDecoder::new(),
+        }
+    }
+
+    pub fn with_protocol(mut self, protocol: Protocol, enabled: bool) -> Self {
+        self.set_protocol(protocol, enabled);
+        self
+    }
+
+    /// Enable or disable the decoding of a protocol
+    ///
+    /// A disabled decoder is reset, i.e. a frame in progress is discarded.
+    pub fn set_protocol(&mut self, protocol: Protocol, enabled: bool) {
+        if enabled {
+            self.enabled |= protocol.mask();
+            return;
+        }
+        self.enabled &= !protocol.mask();
+        match protocol {
+            Protocol::Rc5 => IrDecoder::reset(&mut self.rc5),
+            Protocol::Rc6 => IrDecoder::reset(&mut self.rc6),
+            Protocol::Nec => IrDecoder::reset(&mut self.nec),
+            Protocol::Sirc => IrDecoder::reset(&mut self.sirc),
+        }
+    }
+
+    pub fn is_enabled(&self, protocol: Protocol) -> bool {
+        self.enabled & protocol.mask() != 0
+    }
+}
+
+impl IrDecoder for IrReceiver {
+    type Frame = IrFrame;
+
+    fn next(&mut self, carrier: bool, duration_us: u32) -> Option<IrFrame> {
+        let mut frame = None;
+        for protocol in Protocol::ALL {
+            if !self.is_enabled(protocol) {
+                continue;
+            }
+            let decoded = match protocol {
+                Protocol::Rc5 => self.rc5.next(carrier, duration_us).map(IrFrame::Rc5),
+                Protocol::Rc6 => self.rc6.next(carrier, duration_us).map(IrFrame::Rc6),
+                Protocol::Nec => self.nec.next(carrier, duration_us).map(IrFrame::Nec),
+                Protocol::Sirc => self.sirc.next(carrier, duration_us).map(IrFrame::Sirc),
+            };
+            frame = frame.or(decoded);
+        }
+        frame
+    }
+
+    fn reset(&mut self) {
+        IrDecoder::reset(&mut self.rc5);
+        IrDecoder::reset(&mut self.rc6);
+        IrDecoder::reset(&mut self.nec);
+        IrDecoder::reset(&mut self.sirc);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use super::*;
+    use crate::infrared::nec::{NecEncoder, NecFrame};
+    use crate::infrared::rc5::Rc5Encoder;
+    use crate::infrared::rc6::Rc6Encoder;
+    use crate::infrared::sirc::{SircBits, SircEncoder};
+    use std::vec::Vec;
+
+    const PAUSE: (bool, u32) = (false, 30_000);
+
+    fn receive(
+        receiver: &mut IrReceiver,
+        pulses: impl Iterator<Item = (bool, u32)>,
+    ) -> Vec<IrFrame> {
+        pulses
+            .chain(core::iter::once(PAUSE))
+            .filter_map(|(carrier, duration)| receiver.next(carrier, duration))
+            .collect()
+    }
+
+    #[test]
+    fn detect_protocol() {
+        let mut receiver = IrReceiver::new();
+        let nec = NecFrame::new(0x00, 0x45);
+        let rc6 = Rc6Frame::new(0x00, 0x0c, true);
+        let sirc = SircFrame::new(SircBits::Twelve, 21, 1, 0);
+        let rc5 = Rc5Frame::new(0x03, 0x35, false);
+        let received: Vec<IrFrame> = receive(&mut receiver, NecEncoder::new(nec))
+            .into_iter()
+            .chain(receive(&mut receiver, NecEncoder::repeat()))
+            .chain(receive(&mut receiver, Rc6Encoder::new(rc6)))
+            .chain(receive(&mut receiver, SircEncoder::new(sirc)))
+            .chain(receive(&mut receiver, Rc5Encoder::new(rc5)))
+            .collect();
+        let protocols: Vec<Protocol> = received.iter().map(|frame| frame.protocol()).collect();
+        assert_eq!(
+            protocols,
+            [
+                Protocol::Nec,
+                Protocol::Nec,
+                Protocol::Rc6,
+                Protocol::Sirc,
+                Protocol::Rc5
+            ]
+        );
+        assert_eq!(received[1], IrFrame::Nec(NecCode::Repeat(nec)));
+        assert_eq!(received[2], IrFrame::Rc6(rc6));
+        assert_eq!(received[3], IrFrame::Sirc(sirc));
+        assert_eq!(received[4], IrFrame::Rc5(rc5));
+    }
+
+    #[test]
+    fn disabled_protocols_are_ignored() {
+        let mut receiver = IrReceiver::new().with_protocol(Protocol::Nec, false);
+        assert!(!receiver.is_enabled(Protocol::Nec));
+        let nec = NecFrame::new(0x00, 0x45);
+        assert!(receive(&mut receiver, NecEncoder::new(nec)).is_empty());
+        receiver.set_protocol(Protocol::Nec, true);
+        assert_eq!(
+            receive(&mut receiver, NecEncoder::new(nec)),
+            [IrFrame::Nec(NecCode::Frame(nec))]
+        );
+    }
+
+    #[test]
+    fn disabling_discards_a_frame_in_progress() {
+        let mut receiver = IrReceiver::new();
+        let nec = NecFrame::new(0x00, 0x45);
+        let pulses: Vec<(bool, u32)> = NecEncoder::new(nec).collect();
+        let (start, rest) = pulses.split_at(20);
+        for (carrier, duration) in start {
+            assert_eq!(receiver.next(*carrier, *duration), None);
+        }
+        receiver.set_protocol(Protocol::Nec, false);
+        receiver.set_protocol(Protocol::Nec, true);
+        // the rest of the frame is not taken for a complete one
+        assert!(receive(&mut receiver, rest.iter().copied()).is_empty());
+        assert_eq!(
+            receive(&mut receiver, NecEncoder::new(nec)),
+            [IrFrame::Nec(NecCode::Frame(nec))]
+        );
+    }
+
+    #[test]
+    fn frames_match_only_their_protocol() {
+        let frames = [
+            IrFrame::Rc5(Rc5Frame::new(0x03, 0x35, false)),
+            IrFrame::Rc6(Rc6Frame::new(0x00, 0x0c, true)),
+            IrFrame::Nec(NecCode::Frame(NecFrame::new(0x00, 0x45))),
+            IrFrame::Sirc(SircFrame::new(SircBits::Twelve, 21, 1, 0)),
+        ];
+        for frame in frames {
+            for protocol in Protocol::ALL {
+                let mut receiver = IrReceiver::new();
+                for other in Protocol::ALL {
+                    receiver.set_protocol(other, other == protocol);
+                }
+                let pulses: Vec<(bool, u32)> = match frame {
+                    IrFrame::Rc5(frame) => Rc5Encoder::new(frame).collect(),
+                    IrFrame::Rc6(frame) => Rc6Encoder::new(frame).collect(),
+                    IrFrame::Nec(NecCode::Frame(frame)) => NecEncoder::new(frame).collect(),
+                    IrFrame::Nec(NecCode::Repeat(_)) => NecEncoder::repeat().collect(),
+                    IrFrame::Sirc(frame) => SircEncoder::new(frame).collect(),
+                };
+                let received = receive(&mut receiver, pulses.into_iter());
+                if frame.protocol() == protocol {
+                    assert_eq!(received, [frame]);
+                } else {
+                    assert!(received.is_empty(), "{:?} decoded as {:?}", frame, protocol);
+                }
+            }
+        }
+    }
+}
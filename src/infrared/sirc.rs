@@ -0,0 +1,319 @@
+//!
+//! Sony SIRC protocol with 12, 15 and 20 bit frames
+//!
+//! SIRC uses pulse width coding on a 40 kHz carrier. A frame starts with a
+//! 2.4 ms header mark, followed by bits sent LSB first. Every mark is
+//! preceded by a 600 µs space and lasts 1200 µs for a 1 and 600 µs for a 0:
+//!
+//! | command (7 bit) | address (5 bit) |                      12 bit frame
+//! | command (7 bit) | address (8 bit) |                      15 bit frame
+//! | command (7 bit) | address (5 bit) | extended (8 bit) |   20 bit frame
+//!
+//! Frames are sent at least three times, starting every 45 ms. The length
+//! of a frame is only known once the pause after it is seen, so call
+//! `timeout` if no edge arrives within a few ms.
+//!
+//! ```ignore
+//! let mut receiver = SircDecoder::new();
+//! if let Some(frame) = receiver.next(carrier, duration_us) {
+//!     defmt::println!("{}", frame);
+//! }
+//! ```
+
+use super::{within, IrDecoder};
+
+/// Carrier frequency in Hz
+pub const CARRIER_HZ: u32 = 40_000;
+/// Period of repeated frames in ms
+pub const REPEAT_PERIOD_MS: u32 = 45;
+/// All marks and spaces are multiples of this unit in µs
+pub const UNIT_US: u32 = 600;
+
+const HEADER_MARK_US: u32 = 2400;
+const SPACE_US: u32 = 600;
+const ZERO_MARK_US: u32 = 600;
+const ONE_MARK_US: u32 = 1200;
+const COMMAND_BITS: u8 = 7;
+
+/// Frame length
+#[derive(Clone, Copy, PartialEq, Debug, defmt::Format)]
+pub enum SircBits {
+    Twelve,
+    Fifteen,
+    Twenty,
+}
+
+impl SircBits {
+    /// Number of bits of a frame
+    pub fn count(&self) -> u8 {
+        match self {
+            SircBits::Twelve => 12,
+            SircBits::Fifteen => 15,
+            SircBits::Twenty => 20,
+        }
+    }
+
+    fn from_count(count: u8) -> Option<Self> {
+        match count {
+            12 => Some(SircBits::Twelve),
+            15 => Some(SircBits::Fifteen),
+            20 => Some(SircBits::Twenty),
+            _ => None,
+        }
+    }
+}
+
+/// Content of a SIRC frame
+#[derive(Clone, Copy, PartialEq, Debug, defmt::Format)]
+pub struct SircFrame {
+    pub bits: SircBits,
+    /// Command 0..=127
+    pub command: u8,
+    /// Device address, 0..=255 for 15 bit frames, 0..=31 otherwise
+    pub address: u8,
+    /// Extended field of 20 bit frames, 0 otherwise
+    pub extended: u8,
+}
+
+impl SircFrame {
+    /// Frame of the given length, the extended field is ignored unless it
+    /// is a 20 bit frame
+    pub fn new(bits: SircBits, command: u8, address: u8, extended: u8) -> Self {
+        if command > 127 {
+            panic!("SIRC command out of range");
+        }
+        if bits != SircBits::Fifteen && address > 31 {
+            panic!("SIRC address out of range");
+        }
+        SircFrame {
+            bits,
+            command,
+            address,
+            extended: if bits == SircBits::Twenty {
+                extended
+            } else {
+                0
+            },
+        }
+    }
+
+    /// The bits in transmission order, LSB first
+    fn data(&self) -> u32 {
+        self.command as u32
+            | (self.address as u32) << COMMAND_BITS
+            | (self.extended as u32) << (COMMAND_BITS + 5)
+    }
+
+    fn from_data(bits: SircBits, data: u32) -> Self {
+        let command = (data & 0x7f) as u8;
+        let address = (data >> COMMAND_BITS) as u8;
+        match bits {
+            SircBits::Fifteen => SircFrame::new(bits, command, address, 0),
+            _ => SircFrame::new(bits, command, address & 0x1f, (data >> 12) as u8),
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum State {
+    Idle,
+    /// Header mark or bit mark received, a space follows
+    Space,
+    Mark,
+}
+
+pub struct SircDecoder {
+    state: State,
+    data: u32,
+    count: u8,
+}
+
+impl Default for SircDecoder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SircDecoder {
+    pub const fn new() -> Self {
+        SircDecoder {
+            state: State::Idle,
+            data: 0,
+            count: 0,
+        }
+    }
+
+    /// Process the duration of a mark or space that just ended
+    ///
+    /// Args:
+    /// * carrier - true for a mark, false for a space
+    /// * duration_us - duration in µs
+    ///
+    /// Returns:
+    /// * Some(frame) - the pause after a frame is seen
+    pub fn next(&mut self, carrier: bool, duration_us: u32) -> Option<SircFrame> {
+        let mut frame = None;
+        self.state = match (self.state, carrier) {
+            (_, true) if within(duration_us, HEADER_MARK_US) => {
+                self.data = 0;
+                self.count = 0;
+                State::Space
+            }
+            (State::Space, false) if within(duration_us, SPACE_US) => State::Mark,
+            (State::Space, false) if duration_us > SPACE_US => {
+                frame = self.timeout();
+                State::Idle
+            }
+            (State::Mark, true) if self.count < 20 && within(duration_us, ZERO_MARK_US) => {
+                self.count += 1;
+                State::Space
+            }
+            (State::Mark, true) if self.count < 20 && within(duration_us, ONE_MARK_US) => {
+                self.data |= 1 << self.count;
+                self.count += 1;
+                State::Space
+            }
+            _ => State::Idle,
+        };
+        frame
+    }
+
+    /// Complete the frame received last, call it if no edge arrives for
+    /// more than 1.2 ms
+    pub fn timeout(&mut self) -> Option<SircFrame> {
+        let frame = match self.state {
+            State::Space => {
+                SircBits::from_count(self.count).map(|bits| SircFrame::from_data(bits, self.data))
+            }
+            _ => None,
+        };
+        self.state = State::Idle;
+        frame
+    }
+}
+
+impl IrDecoder for SircDecoder {
+    type Frame = SircFrame;
+
+    fn next(&mut self, carrier: bool, duration_us: u32) -> Option<SircFrame> {
+        SircDecoder::next(self, carrier, duration_us)
+    }
+
+    fn reset(&mut self) {
+        self.state = State::Idle;
+    }
+}
+
+/// Marks and spaces of a frame as (carrier, duration in µs)
+///
+/// The frame ends with a mark, the next frame starts 45 ms after this one.
+pub struct SircEncoder {
+    data: u32,
+    count: u8,
+    step: u8,
+}
+
+impl SircEncoder {
+    pub fn new(frame: SircFrame) -> Self {
+        SircEncoder {
+            data: frame.data(),
+            count: frame.bits.count(),
+            step: 0,
+        }
+    }
+}
+
+impl Iterator for SircEncoder {
+    type Item = (bool, u32);
+
+    fn next(&mut self) -> Option<(bool, u32)> {
+        let pulse = match self.step {
+            0 => (true, HEADER_MARK_US),
+            step if step > 2 * self.count => return None,
+            step if step % 2 == 1 => (false, SPACE_US),
+            step => {
+                if self.data & (1 << (step / 2 - 1)) != 0 {
+                    (true, ONE_MARK_US)
+                } else {
+                    (true, ZERO_MARK_US)
+                }
+            }
+        };
+        self.step += 1;
+        Some(pulse)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use super::*;
+    use std::vec::Vec;
+
+    /// Frames sent three times with pauses in between
+    fn receive(frame: SircFrame) -> Vec<SircFrame> {
+        let mut decoder = SircDecoder::new();
+        let pause = core::iter::once((false, 20_000));
+        let pulses = SircEncoder::new(frame)
+            .chain(pause.clone())
+            .chain(SircEncoder::new(frame))
+            .chain(pause)
+            .chain(SircEncoder::new(frame));
+        let mut frames: Vec<SircFrame> = pulses
+            .filter_map(|(carrier, duration)| decoder.next(carrier, duration))
+            .collect();
+        frames.extend(decoder.timeout());
+        frames
+    }
+
+    #[test]
+    fn encode_and_decode() {
+        // power of a TV: command 21, address 1
+        let frame = SircFrame::new(SircBits::Twelve, 21, 1, 0);
+        let pulses: Vec<(bool, u32)> = SircEncoder::new(frame).collect();
+        assert_eq!(pulses.len(), 25);
+        assert_eq!(
+            pulses[..6],
+            [
+                (true, 2400),
+                (false, 600),
+                (true, 1200),
+                (false, 600),
+                (true, 600),
+                (false, 600)
+            ]
+        );
+        assert_eq!(pulses.last(), Some(&(true, 600)));
+        assert_eq!(receive(frame), [frame; 3]);
+
+        for frame in [
+            SircFrame::new(SircBits::Fifteen, 0x7f, 0xa4, 0),
+            SircFrame::new(SircBits::Twenty, 0x12, 0x1a, 0xe5),
+        ] {
+            assert_eq!(receive(frame), [frame; 3]);
+        }
+    }
+
+    #[test]
+    fn reject_other_lengths_and_glitches() {
+        let mut decoder = SircDecoder::new();
+        let frame = SircFrame::new(SircBits::Fifteen, 0x01, 0x02, 0);
+        // 13 bits received
+        let pulses = SircEncoder::new(frame).take(27);
+        assert!(pulses
+            .filter_map(|(c, d)| decoder.next(c, d))
+            .next()
+            .is_none());
+        assert_eq!(decoder.timeout(), None);
+        // glitch within a frame
+        let mut pulses: Vec<(bool, u32)> = SircEncoder::new(frame).collect();
+        pulses[9].1 = 900;
+        assert!(pulses
+            .into_iter()
+            .filter_map(|(c, d)| decoder.next(c, d))
+            .next()
+            .is_none());
+        assert_eq!(decoder.timeout(), None);
+    }
+}
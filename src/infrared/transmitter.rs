@@ -0,0 +1,726 @@
+//!
+//! Transmitter of all supported protocols
+//!
+//! `IrTransmitter` owns the carrier (a PWM channel driving the infrared LED)
+//! and a bit clock timer, which is restarted with the duration of every mark
+//! and space. Frames are queued by `send` and sent by `on_interrupt`, which
+//! the application calls from the interrupt handler of the timer. Repeats of
+//! a frame follow at the repeat period of its protocol, queued frames are
+//! separated by a gap. The clock is stopped once the gap after the last frame
+//! elapsed and restarted by `send`. The carrier frequency and duty cycle are
+//! configured per protocol or per frame and changed between frames.
+//!
+//! ```ignore
+//! #[interrupt]
+//! fn TIM2() {
+//!     free(|cs| TRANSMITTER.borrow(cs).borrow_mut().as_mut().unwrap().on_interrupt());
+//! }
+//!
+//! let sequence = block!(free(|cs| transmitter.send(frame, 2))).unwrap();
+//! while !free(|cs| transmitter.is_sent(sequence)) {}
+//! ```
+
+use core::convert::Infallible;
+
+use crate::hal::hal::{timer::CountDown, PwmPin as _};
+use crate::hal::rcc::Rcc;
+use crate::hal::stm32::{TIM1, TIM14, TIM15, TIM16, TIM17, TIM2, TIM3, TIM6, TIM7};
+use crate::hal::time::{Hertz, MicroSecond};
+use crate::hal::timer::pwm::{Pwm, PwmPin};
+use crate::hal::timer::{Channel1, Timer};
+
+use super::nec::{NecCode, NecEncoder};
+use super::rc5::Rc5Encoder;
+use super::rc6::Rc6Encoder;
+use super::receiver::{IrFrame, Protocol};
+use super::sirc::SircEncoder;
+
+/// Default gap between queued frames in µs
+pub const DEFAULT_GAP_US: u32 = 30_000;
+
+/// Default duty cycle of the carrier in percent
+pub const DEFAULT_DUTY_PERCENT: u8 = 25;
+
+/// Frequency and duty cycle of the carrier
+#[derive(Clone, Copy, PartialEq, Debug, defmt::Format)]
+pub struct CarrierConfig {
+    frequency_hz: u32,
+    duty_percent: u8,
+}
+
+impl CarrierConfig {
+    /// Carrier of 30 to 56 kHz with a duty cycle of 10 to 50%
+    pub fn new(frequency_hz: u32, duty_percent: u8) -> Self {
+        assert!(
+            (30_000..=56_000).contains(&frequency_hz),
+            "carrier frequency out of range"
+        );
+        assert!((10..=50).contains(&duty_percent), "duty cycle out of range");
+        CarrierConfig {
+            frequency_hz,
+            duty_percent,
+        }
+    }
+
+    /// Nominal frequency of the protocol with the default duty cycle
+    pub fn protocol(protocol: Protocol) -> Self {
+        CarrierConfig::new(protocol.carrier_hz(), DEFAULT_DUTY_PERCENT)
+    }
+
+    /// Nominal frequency in Hz
+    pub fn frequency_hz(&self) -> u32 {
+        self.frequency_hz
+    }
+
+    pub fn duty_percent(&self) -> u8 {
+        self.duty_percent
+    }
+
+    /// Frequency in Hz achieved by a PWM timer running at the clock,
+    /// rounded like `Pwm::set_freq` does
+    pub fn achieved_hz(&self, timer_clock_hz: u32) -> u32 {
+        let (psc, arr) = self.prescaler(timer_clock_hz);
+        timer_clock_hz / ((psc + 1) * (arr + 1))
+    }
+
+    /// Prescaler and auto reload value of a PWM timer
+    fn prescaler(&self, timer_clock_hz: u32) -> (u32, u32) {
+        let ratio = timer_clock_hz / self.frequency_hz;
+        let psc = (ratio - 1) / 0xffff;
+        (psc, ratio / (psc + 1) - 1)
+    }
+}
+
+/// Modulated output of the infrared LED
+pub trait Carrier {
+    /// Change frequency and duty cycle, called while the carrier is off
+    ///
+    /// Returns the achieved frequency in Hz
+    fn configure(&mut self, config: CarrierConfig) -> u32;
+    fn on(&mut self);
+    fn off(&mut self);
+}
+
+/// Timer interrupting after the duration of a mark or space
+pub trait BitClock {
+    /// Restart with a duration
+    fn start(&mut self, duration_us: u32);
+    /// Clear the interrupt flag
+    fn clear(&mut self);
+    /// Stop interrupting until the next start
+    fn stop(&mut self);
+}
+
+macro_rules! bit_clocks {
+    ($($TIM:ident,)+) => {
+        $(
+            impl BitClock for Timer<$TIM> {
+                fn start(&mut self, duration_us: u32) {
+                    CountDown::start(self, MicroSecond(duration_us));
+                }
+
+                fn clear(&mut self) {
+                    self.wait().ok();
+                }
+
+                fn stop(&mut self) {
+                    self.pause();
+                }
+            }
+        )+
+    }
+}
+
+bit_clocks! {
+    TIM1, TIM2, TIM3, TIM6, TIM7, TIM14, TIM15, TIM16, TIM17,
+}
+
+/// Carrier by PWM of TIM3
+pub struct PwmCarrier<CH> {
+    pwm: Pwm<TIM3>,
+    pin: PwmPin<TIM3, CH>,
+    clock_hz: u32,
+}
+
+impl<CH> PwmCarrier<CH>
+where
+    PwmPin<TIM3, CH>: crate::hal::hal::PwmPin<Duty = u32>,
+{
+    pub fn new(pwm: Pwm<TIM3>, mut pin: PwmPin<TIM3, CH>, rcc: &Rcc) -> Self {
+        pin.disable();
+        PwmCarrier {
+            pwm,
+            pin,
+            clock_hz: rcc.clocks.apb_tim_clk.0,
+        }
+    }
+
+    /// Frequency in Hz the configuration would achieve
+    pub fn achieved_hz(&self, config: CarrierConfig) -> u32 {
+        config.achieved_hz(self.clock_hz)
+    }
+
+    pub fn release(self) -> (Pwm<TIM3>, PwmPin<TIM3, CH>) {
+        (self.pwm, self.pin)
+    }
+}
+
+impl PwmCarrier<Channel1> {
+    /// Prepare for marks and spaces written to the compare register by DMA,
+    /// the output is enabled with a duty cycle of zero
+    ///
+    /// Returns the address of the compare register and the compare value of
+    /// a mark with the configured duty cycle
+    pub(super) fn dma_target(&mut self) -> (u32, u16) {
+        let mark = self.pin.get_duty() as u16;
+        self.pin.set_duty(0);
+        self.pin.enable();
+        // NOTE(unsafe) the register of channel 1 belongs to the pin
+        let address = unsafe { &(*TIM3::ptr()).ccr1 as *const _ as u32 };
+        (address, mark)
+    }
+}
+
+impl<CH> Carrier for PwmCarrier<CH>
+where
+    PwmPin<TIM3, CH>: crate::hal::hal::PwmPin<Duty = u32>,
+{
+    fn configure(&mut self, config: CarrierConfig) -> u32 {
+        self.pwm.set_freq(Hertz(config.frequency_hz()));
+        // the duty cycle depends on the period
+        let period = self.pin.get_max_duty() + 1;
+        self.pin
+            .set_duty(period * config.duty_percent() as u32 / 100);
+        self.achieved_hz(config)
+    }
+
+    fn on(&mut self) {
+        self.pin.enable();
+    }
+
+    fn off(&mut self) {
+        self.pin.disable();
+    }
+}
+
+/// Marks and spaces of a frame of any protocol
+pub(super) enum Pulses {
+    Rc5(Rc5Encoder),
+    Rc6(Rc6Encoder),
+    Nec(NecEncoder),
+    Sirc(SircEncoder),
+}
+
+impl Pulses {
+    /// Pulses of a frame, NEC frames are repeated by repeat codes
+    pub(super) fn new(frame: &IrFrame, repeat: bool) -> Self {
+        match *frame {
+            IrFrame::Rc5(frame) => Pulses::Rc5(Rc5Encoder::new(frame)),
+            IrFrame::Rc6(frame) => Pulses::Rc6(Rc6Encoder::new(frame)),
+            IrFrame::Nec(NecCode::Frame(frame)) if !repeat => Pulses::Nec(NecEncoder::new(frame)),
+            IrFrame::Nec(_) => Pulses::Nec(NecEncoder::repeat()),
+            IrFrame::Sirc(frame) => Pulses::Sirc(SircEncoder::new(frame)),
+        }
+    }
+}
+
+impl Iterator for Pulses {
+    type Item = (bool, u32);
+
+    fn next(&mut self) -> Option<(bool, u32)> {
+        match self {
+            Pulses::Rc5(encoder) => encoder.next(),
+            Pulses::Rc6(encoder) => encoder.next(),
+            Pulses::Nec(encoder) => encoder.next(),
+            Pulses::Sirc(encoder) => encoder.next(),
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+struct Queued {
+    frame: IrFrame,
+    repeats: u8,
+    carrier: CarrierConfig,
+}
+
+/// Frame being sent
+struct Active {
+    queued: Queued,
+    pulses: Pulses,
+    /// Time since the start of the current repetition in µs
+    elapsed_us: u32,
+}
+
+/// Sends queued frames by a carrier and a bit clock, up to N frames wait
+pub struct IrTransmitter<C, T, const N: usize> {
+    carrier: C,
+    clock: T,
+    gap_us: u32,
+    /// Carrier of each protocol
+    carriers: [CarrierConfig; 4],
+    /// Current carrier and its achieved frequency
+    configured: Option<(CarrierConfig, u32)>,
+    queue: [Option<Queued>; N],
+    first: usize,
+    len: usize,
+    active: Option<Active>,
+    /// Sequence number of the next frame
+    sequence: u32,
+    /// Number of frames sent
+    completed: u32,
+    /// False while the clock is stopped
+    running: bool,
+    callback: Option<fn()>,
+}
+
+impl<C: Carrier, T: BitClock, const N: usize> IrTransmitter<C, T, N> {
+    /// Transmitter with the default gap, the clock interrupt must be enabled
+    pub fn new(mut carrier: C, mut clock: T) -> Self {
+        carrier.off();
+        clock.stop();
+        IrTransmitter {
+            carrier,
+            clock,
+            gap_us: DEFAULT_GAP_US,
+            carriers: Protocol::ALL.map(CarrierConfig::protocol),
+            configured: None,
+            queue: [None; N],
+            first: 0,
+            len: 0,
+            active: None,
+            sequence: 0,
+            completed: 0,
+            running: false,
+            callback: None,
+        }
+    }
+
+    /// Pause between the end of a frame and the start of the next queued
+    /// one in µs, a frame queued later starts right away
+    pub fn with_gap(mut self, gap_us: u32) -> Self {
+        assert!(gap_us > 0, "gap must not be zero");
+        self.gap_us = gap_us;
+        self
+    }
+
+    /// Function called by `on_interrupt` once a frame and its repeats are sent
+    pub fn with_callback(mut self, callback: fn()) -> Self {
+        self.callback = Some(callback);
+        self
+    }
+
+    pub fn with_carrier(mut self, protocol: Protocol, config: CarrierConfig) -> Self {
+        self.set_carrier(protocol, config);
+        self
+    }
+
+    /// Carrier of the frames of a protocol queued from now on
+    pub fn set_carrier(&mut self, protocol: Protocol, config: CarrierConfig) {
+        self.carriers[protocol as usize] = config;
+    }
+
+    pub fn carrier(&self, protocol: Protocol) -> CarrierConfig {
+        self.carriers[protocol as usize]
+    }
+
+    /// Achieved frequency in Hz of the carrier of the frame being or last
+    /// sent, None before the first frame
+    pub fn carrier_hz(&self) -> Option<u32> {
+        self.configured.map(|(_, hz)| hz)
+    }
+
+    /// Queue a frame with the carrier of its protocol, followed by a number
+    /// of repeats
+    ///
+    /// Returns:
+    /// * Ok(sequence) - sequence number of the frame for `is_sent`
+    /// * Err(WouldBlock) - the queue is full
+    pub fn send(&mut self, frame: IrFrame, repeats: u8) -> nb::Result<u32, Infallible> {
+        let carrier = self.carrier(frame.protocol());
+        self.send_with_carrier(frame, repeats, carrier)
+    }
+
+    /// Queue a frame with its own carrier, followed by a number of repeats
+    ///
+    /// The frame starts right away if the transmitter is idle.
+    pub fn send_with_carrier(
+        &mut self,
+        frame: IrFrame,
+        repeats: u8,
+        carrier: CarrierConfig,
+    ) -> nb::Result<u32, Infallible> {
+        if self.len == N {
+            return Err(nb::Error::WouldBlock);
+        }
+        let sequence = self.sequence;
+        self.queue[(self.first + self.len) % N] = Some(Queued {
+            frame,
+            repeats,
+            carrier,
+        });
+        self.len += 1;
+        self.sequence = self.sequence.wrapping_add(1);
+        if !self.running {
+            self.running = true;
+            self.advance();
+        }
+        Ok(sequence)
+    }
+
+    /// True once the frame with the sequence number and its repeats are sent
+    pub fn is_sent(&self, sequence: u32) -> bool {
+        self.completed.wrapping_sub(sequence) as i32 > 0
+    }
+
+    /// Number of queued frames sent, a frame counts once with all its repeats
+    pub fn completed(&self) -> u32 {
+        self.completed
+    }
+
+    /// True if nothing is sent or queued
+    pub fn is_idle(&self) -> bool {
+        self.active.is_none() && self.len == 0
+    }
+
+    /// Advance to the next mark or space, to be called by the interrupt
+    /// handler of the bit clock
+    pub fn on_interrupt(&mut self) {
+        self.clock.clear();
+        self.advance();
+    }
+
+    /// Stop and return the carrier and the clock, queued frames are dropped
+    pub fn release(mut self) -> (C, T) {
+        self.carrier.off();
+        self.clock.stop();
+        (self.carrier, self.clock)
+    }
+
+    /// Output the next mark or space and start the clock with its duration
+    fn advance(&mut self) {
+        if self.active.is_none() {
+            let queued = match self.pop() {
+                Some(queued) => queued,
+                // the gap after the last frame elapsed
+                None => {
+                    self.clock.stop();
+                    self.running = false;
+                    return;
+                }
+            };
+            if self.configured.map(|(config, _)| config) != Some(queued.carrier) {
+                let achieved = self.carrier.configure(queued.carrier);
+                self.configured = Some((queued.carrier, achieved));
+            }
+            self.active = Some(Active {
+                queued,
+                pulses: Pulses::new(&queued.frame, false),
+                elapsed_us: 0,
+            });
+        }
+        let active = self.active.as_mut().unwrap();
+        match active.pulses.next() {
+            Some((carrier, duration)) => {
+                if carrier {
+                    self.carrier.on();
+                } else {
+                    self.carrier.off();
+                }
+                active.elapsed_us += duration;
+                self.clock.start(duration);
+            }
+            None => {
+                self.carrier.off();
+                if active.queued.repeats > 0 {
+                    active.queued.repeats -= 1;
+                    active.pulses = Pulses::new(&active.queued.frame, true);
+                    let period = active.queued.frame.protocol().repeat_period_ms() * 1000;
+                    let wait = period.saturating_sub(active.elapsed_us).max(1);
+                    active.elapsed_us = 0;
+                    self.clock.start(wait);
+                } else {
+                    self.active = None;
+                    self.completed = self.completed.wrapping_add(1);
+                    self.clock.start(self.gap_us);
+                    if let Some(callback) = self.callback {
+                        callback();
+                    }
+                }
+            }
+        }
+    }
+
+    fn pop(&mut self) -> Option<Queued> {
+        if self.len == 0 {
+            None
+        } else {
+            let queued = self.queue[self.first].take();
+            self.first = (self.first + 1) % N;
+            self.len -= 1;
+            queued
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use super::*;
+    use crate::infrared::nec::{self, NecFrame};
+    use crate::infrared::rc5::{self, Rc5Frame};
+    use crate::infrared::sirc::{SircBits, SircFrame};
+    use std::cell::{Cell, RefCell};
+    use std::rc::Rc;
+    use std::sync::atomic::{AtomicU32, Ordering};
+    use std::vec::Vec;
+
+    const TIMER_CLOCK_HZ: u32 = 16_000_000;
+
+    #[derive(Clone, Copy, PartialEq, Debug)]
+    enum Output {
+        Carrier(CarrierConfig),
+        On,
+        Off,
+    }
+
+    /// Carrier recording its changes with the time in µs
+    #[derive(Clone, Default)]
+    struct MockCarrier {
+        now: Rc<Cell<u32>>,
+        log: Rc<RefCell<Vec<(u32, Output)>>>,
+        on: bool,
+    }
+
+    impl Carrier for MockCarrier {
+        fn configure(&mut self, config: CarrierConfig) -> u32 {
+            self.log
+                .borrow_mut()
+                .push((self.now.get(), Output::Carrier(config)));
+            config.achieved_hz(TIMER_CLOCK_HZ)
+        }
+
+        fn on(&mut self) {
+            if !self.on {
+                self.on = true;
+                self.log.borrow_mut().push((self.now.get(), Output::On));
+            }
+        }
+
+        fn off(&mut self) {
+            if self.on {
+                self.on = false;
+                self.log.borrow_mut().push((self.now.get(), Output::Off));
+            }
+        }
+    }
+
+    #[derive(Clone, Default)]
+    struct MockClock {
+        period: Rc<Cell<u32>>,
+        running: Rc<Cell<bool>>,
+    }
+
+    impl BitClock for MockClock {
+        fn start(&mut self, duration_us: u32) {
+            self.period.set(duration_us);
+            self.running.set(true);
+        }
+
+        fn clear(&mut self) {}
+
+        fn stop(&mut self) {
+            self.running.set(false);
+        }
+    }
+
+    struct Bench {
+        carrier: MockCarrier,
+        clock: MockClock,
+    }
+
+    impl Bench {
+        fn new() -> Self {
+            Bench {
+                carrier: MockCarrier::default(),
+                clock: MockClock::default(),
+            }
+        }
+
+        fn transmitter<const N: usize>(&self) -> IrTransmitter<MockCarrier, MockClock, N> {
+            IrTransmitter::new(self.carrier.clone(), self.clock.clone())
+        }
+
+        /// Interrupt at the end of every clock period until the time
+        fn run<const N: usize>(
+            &self,
+            transmitter: &mut IrTransmitter<MockCarrier, MockClock, N>,
+            until_us: u32,
+        ) {
+            let now = &self.carrier.now;
+            while self.clock.running.get() && now.get() + self.clock.period.get() <= until_us {
+                now.set(now.get() + self.clock.period.get());
+                transmitter.on_interrupt();
+            }
+            now.set(now.get().max(until_us));
+        }
+
+        fn log(&self) -> Vec<(u32, Output)> {
+            self.carrier.log.borrow().clone()
+        }
+
+        /// Start times of the marks
+        fn marks(&self) -> Vec<u32> {
+            self.log()
+                .iter()
+                .filter(|(_, output)| *output == Output::On)
+                .map(|(time, _)| *time)
+                .collect()
+        }
+    }
+
+    static SENT: AtomicU32 = AtomicU32::new(0);
+
+    fn sent() {
+        SENT.fetch_add(1, Ordering::Relaxed);
+    }
+
+    #[test]
+    fn send_frame_with_repeats() {
+        let bench = Bench::new();
+        let mut transmitter = bench.transmitter::<2>().with_gap(1000).with_callback(sent);
+        assert!(!bench.clock.running.get());
+        let frame = Rc5Frame::new(0, 1, true);
+        let sequence = transmitter.send(IrFrame::Rc5(frame), 2).unwrap();
+        assert!(!transmitter.is_sent(sequence));
+        bench.run(&mut transmitter, 400_000);
+        assert!(transmitter.is_sent(sequence));
+        assert!(transmitter.is_idle());
+        assert_eq!(transmitter.completed(), 1);
+        assert_eq!(SENT.load(Ordering::Relaxed), 1);
+        // stopped after the gap
+        assert!(!bench.clock.running.get());
+
+        let pulses: Vec<(bool, u32)> = Rc5Encoder::new(frame).collect();
+        let marks = pulses.iter().filter(|(carrier, _)| *carrier).count();
+        let log = bench.log();
+        let carrier = CarrierConfig::new(rc5::CARRIER_HZ, DEFAULT_DUTY_PERCENT);
+        // started right away
+        assert_eq!(log[0], (0, Output::Carrier(carrier)));
+        assert_eq!(log[1], (0, Output::On));
+        assert_eq!(log.len(), 1 + 3 * 2 * marks);
+        // the marks and spaces of the first frame
+        let first: Vec<(bool, u32)> = log[1..=2 * marks]
+            .windows(2)
+            .map(|pair| (pair[0].1 == Output::On, pair[1].0 - pair[0].0))
+            .collect();
+        assert_eq!(first, pulses);
+        // repeated at the repeat period
+        let starts: Vec<u32> = bench.marks().into_iter().step_by(marks).collect();
+        let period = rc5::REPEAT_PERIOD_MS * 1000;
+        assert_eq!(starts, [0, period, 2 * period]);
+
+        // restarted by the next frame
+        transmitter.send(IrFrame::Rc5(frame), 0).unwrap();
+        assert!(bench.clock.running.get());
+        assert_eq!(bench.log()[log.len()], (400_000, Output::On));
+    }
+
+    #[test]
+    fn queued_frames_are_separated_by_the_gap() {
+        let bench = Bench::new();
+        let mut transmitter = bench.transmitter::<2>().with_gap(20_000);
+        let nec = NecFrame::new(0x00, 0x46);
+        let sirc = SircFrame::new(SircBits::Twelve, 18, 1, 0);
+        // the first frame starts right away and leaves the queue
+        let first = transmitter.send(IrFrame::Nec(NecCode::Frame(nec)), 1);
+        let second = transmitter.send(IrFrame::Sirc(sirc), 0);
+        let third = transmitter.send(IrFrame::Sirc(sirc), 0);
+        assert_eq!((first, second, third), (Ok(0), Ok(1), Ok(2)));
+        // backpressure
+        assert_eq!(
+            transmitter.send(IrFrame::Sirc(sirc), 0),
+            Err(nb::Error::WouldBlock)
+        );
+        bench.run(&mut transmitter, 1_000_000);
+        assert_eq!(transmitter.completed(), 3);
+
+        let frequencies: Vec<(u32, Output)> = bench
+            .log()
+            .into_iter()
+            .filter(|(_, output)| matches!(output, Output::Carrier(_)))
+            .collect();
+        let nec_end = nec::REPEAT_PERIOD_MS * 1000 + 9000 + 2250 + 562;
+        assert_eq!(
+            frequencies,
+            [
+                (0, Output::Carrier(CarrierConfig::protocol(Protocol::Nec))),
+                (
+                    nec_end + 20_000,
+                    Output::Carrier(CarrierConfig::protocol(Protocol::Sirc))
+                ),
+            ]
+        );
+        // NEC frame, repeat code, two SIRC frames of 13 marks each, the log
+        // has a frequency change before the NEC and the first SIRC frame
+        let marks = bench.marks();
+        assert_eq!(marks.len(), 34 + 2 + 2 * 13);
+        assert_eq!(marks[34], nec::REPEAT_PERIOD_MS * 1000);
+        assert_eq!(marks[36], nec_end + 20_000);
+        let sirc_end = bench.log()[2 * 36 + 2 + 2 * 13 - 1].0;
+        assert_eq!(marks[49], sirc_end + 20_000);
+    }
+
+    #[test]
+    fn achieved_carrier_frequency() {
+        let achieved = |hz| CarrierConfig::new(hz, 25).achieved_hz(TIMER_CLOCK_HZ);
+        assert_eq!(achieved(36_000), 36_036);
+        assert_eq!(achieved(38_000), 38_004);
+        assert_eq!(achieved(40_000), 40_000);
+        assert_eq!(
+            CarrierConfig::new(38_000, 33).achieved_hz(64_000_000),
+            38_004
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "carrier frequency out of range")]
+    fn carrier_out_of_range() {
+        CarrierConfig::new(60_000, 25);
+    }
+
+    #[test]
+    #[should_panic(expected = "duty cycle out of range")]
+    fn duty_cycle_out_of_range() {
+        CarrierConfig::new(38_000, 60);
+    }
+
+    #[test]
+    fn carrier_per_protocol_and_frame() {
+        let bench = Bench::new();
+        let nec = CarrierConfig::new(38_000, 33);
+        let mut transmitter = bench
+            .transmitter::<4>()
+            .with_gap(1000)
+            .with_carrier(Protocol::Nec, nec);
+        assert_eq!(transmitter.carrier(Protocol::Nec), nec);
+        assert_eq!(transmitter.carrier_hz(), None);
+        let frame = IrFrame::Nec(NecCode::Frame(NecFrame::new(0x00, 0x46)));
+        let other = CarrierConfig::new(40_000, 50);
+        transmitter.send(frame, 0).unwrap();
+        transmitter.send(frame, 0).unwrap();
+        transmitter.send_with_carrier(frame, 0, other).unwrap();
+        assert_eq!(transmitter.carrier_hz(), Some(38_004));
+        bench.run(&mut transmitter, 1_000_000);
+        assert_eq!(transmitter.carrier_hz(), Some(40_000));
+        // reconfigured between frames only if the carrier changes
+        let carriers: Vec<Output> = bench
+            .log()
+            .into_iter()
+            .map(|(_, output)| output)
+            .filter(|output| matches!(output, Output::Carrier(_)))
+            .collect();
+        assert_eq!(carriers, [Output::Carrier(nec), Output::Carrier(other)]);
+    }
+}
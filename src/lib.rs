@@ -20,5 +20,14 @@ pub fn exit() -> ! {
     }
 }
 
+pub mod i2c;
+
+pub mod infrared;
+
 pub mod grove_lcd_rgb_backlight;
 pub use grove_lcd_rgb_backlight as lcd;
+
+pub mod ui;
+
+#[cfg(test)]
+mod mock;
@@ -0,0 +1,196 @@
+//!
+//! Simulated Grove LCD RGB backlight on an I2C bus for host tests
+//!
+//! Interprets the commands and data sent to the LCD controller and the
+//! register writes to the backlight controller.
+
+#![allow(dead_code)]
+
+extern crate std;
+
+use std::string::String;
+use std::vec::Vec;
+
+use embedded_hal::blocking::i2c::{Read, Write, WriteRead};
+
+use crate::i2c::TransientError;
+
+const LCD_ADDRESS: u8 = 0x3e;
+const RGB_ADDRESS: u8 = 0x62;
+
+#[derive(Debug, PartialEq)]
+pub enum Error {
+    Nack,
+}
+
+pub struct I2cMock {
+    /// all transmissions (address, bytes)
+    pub writes: Vec<(u8, Vec<u8>)>,
+    /// number of upcoming transmissions that are not acknowledged
+    pub nacks: usize,
+    pub ddram: [u8; 128],
+    pub cgram: [u8; 64],
+    pub registers: [u8; 16],
+    pub display_control: u8,
+    pub shift: i32,
+    /// further devices on the bus as (address, register, value), other registers read 0
+    pub devices: Vec<(u8, u8, u8)>,
+    address: u8,
+    cgram_selected: bool,
+}
+
+impl I2cMock {
+    pub fn new() -> Self {
+        I2cMock {
+            writes: Vec::new(),
+            nacks: 0,
+            ddram: [b' '; 128],
+            cgram: [0; 64],
+            registers: [0; 16],
+            display_control: 0,
+            shift: 0,
+            devices: Vec::new(),
+            address: 0,
+            cgram_selected: false,
+        }
+    }
+
+    /// Visible text of a row, the 16 columns starting at the display shift
+    pub fn line(&self, row: u8) -> String {
+        (0..16)
+            .map(|col| {
+                let col = (col - self.shift).rem_euclid(40) as u8;
+                self.ddram[(row * 0x40 + col) as usize] as char
+            })
+            .collect()
+    }
+
+    /// Simulate a power loss, memory and registers are lost
+    pub fn power_cycle(&mut self) {
+        self.ddram = [b'#'; 128];
+        self.cgram = [0; 64];
+        self.registers = [0; 16];
+        self.display_control = 0;
+        self.shift = 0;
+        self.address = 0;
+        self.cgram_selected = false;
+    }
+
+    /// Backlight color as (red, green, blue)
+    pub fn color(&self) -> (u8, u8, u8) {
+        (self.registers[4], self.registers[3], self.registers[2])
+    }
+
+    /// Position of the address counter as (col, row)
+    pub fn cursor(&self) -> (u8, u8) {
+        (self.address & 0x3f, self.address >> 6)
+    }
+
+    fn command(&mut self, command: u8) {
+        if command & 0x80 != 0 {
+            self.address = command & 0x7f;
+            self.cgram_selected = false;
+        } else if command & 0x40 != 0 {
+            self.address = command & 0x3f;
+            self.cgram_selected = true;
+        } else if command & 0x20 != 0 {
+            // function set
+        } else if command & 0x10 != 0 {
+            if command & 0x08 != 0 {
+                self.shift += if command & 0x04 != 0 { 1 } else { -1 };
+            }
+        } else if command & 0x08 != 0 {
+            self.display_control = command & 0x07;
+        } else if command == 0x01 {
+            self.ddram = [b' '; 128];
+            self.address = 0;
+            self.shift = 0;
+            self.cgram_selected = false;
+        } else if command == 0x02 {
+            self.address = 0;
+            self.shift = 0;
+        }
+    }
+
+    fn data(&mut self, value: u8) {
+        if self.cgram_selected {
+            self.cgram[self.address as usize] = value;
+            self.address = (self.address + 1) & 0x3f;
+        } else {
+            self.ddram[self.address as usize] = value;
+            self.address = match self.address + 1 {
+                0x28 => 0x40,
+                0x68 => 0x00,
+                next => next,
+            };
+        }
+    }
+}
+
+impl TransientError for Error {
+    fn is_transient(&self) -> bool {
+        true
+    }
+}
+
+impl Write for I2cMock {
+    type Error = Error;
+
+    fn write(&mut self, address: u8, bytes: &[u8]) -> Result<(), Error> {
+        if self.nacks > 0 {
+            self.nacks -= 1;
+            return Err(Error::Nack);
+        }
+        self.writes.push((address, bytes.to_vec()));
+        match address {
+            LCD_ADDRESS => match bytes[0] {
+                0x80 => self.command(bytes[1]),
+                0x40 => bytes[1..].iter().for_each(|value| self.data(*value)),
+                0x00 => bytes[1..].iter().for_each(|command| self.command(*command)),
+                _ => panic!("Unexpected control byte"),
+            },
+            RGB_ADDRESS => self.registers[bytes[0] as usize] = bytes[1],
+            _ => return Err(Error::Nack),
+        }
+        Ok(())
+    }
+}
+
+impl Read for I2cMock {
+    type Error = Error;
+
+    fn read(&mut self, address: u8, buffer: &mut [u8]) -> Result<(), Error> {
+        if address == LCD_ADDRESS {
+            // the LCD controller supports writes only
+            return Err(Error::Nack);
+        }
+        self.write_read(address, &[0], buffer)
+    }
+}
+
+impl WriteRead for I2cMock {
+    type Error = Error;
+
+    fn write_read(&mut self, address: u8, bytes: &[u8], buffer: &mut [u8]) -> Result<(), Error> {
+        if self.nacks > 0 {
+            self.nacks -= 1;
+            return Err(Error::Nack);
+        }
+        let register = bytes[0];
+        let value = match address {
+            LCD_ADDRESS => 0,
+            RGB_ADDRESS => self.registers[(register & 0x0f) as usize],
+            _ => {
+                if !self.devices.iter().any(|device| device.0 == address) {
+                    return Err(Error::Nack);
+                }
+                self.devices
+                    .iter()
+                    .find(|device| device.0 == address && device.1 == register)
+                    .map_or(0, |device| device.2)
+            }
+        };
+        buffer.iter_mut().for_each(|byte| *byte = value);
+        Ok(())
+    }
+}
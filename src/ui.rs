@@ -0,0 +1,137 @@
+//!
+//! User interface building blocks on top of the 16x2 `RgbLCD`
+//!
+//! Widgets render into a `Frame`, a RAM copy of the visible characters, which
+//! is transferred to the display row by row. Frames are drawn at canvas
+//! column 0, i.e. they assume the display is not scrolled.
+
+use core::fmt;
+
+use embedded_hal::blocking::i2c::Write;
+
+use crate::lcd::{charset, RgbLCD};
+
+pub mod alert;
+pub mod ansi;
+pub mod console;
+pub mod encoder;
+pub mod input;
+pub mod layout;
+pub mod menu;
+pub mod screen;
+pub mod screensaver;
+
+/// Number of visible columns
+pub const COLUMNS: usize = 16;
+/// Number of visible rows
+pub const ROWS: usize = 2;
+
+/// Input events of navigation keys or a rotary encoder with push button
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Event {
+    Up,
+    Down,
+    Left,
+    Right,
+    Ok,
+    Back,
+}
+
+/// Character codes of the visible display area
+#[derive(Clone, Copy, PartialEq)]
+pub struct Frame {
+    cells: [[u8; COLUMNS]; ROWS],
+    col: usize,
+    row: usize,
+}
+
+impl Frame {
+    /// Create a blank frame
+    pub const fn new() -> Self {
+        Frame {
+            cells: [[b' '; COLUMNS]; ROWS],
+            col: 0,
+            row: 0,
+        }
+    }
+
+    /// Fill with blanks and move the cursor to the top left corner
+    pub fn clear(&mut self) {
+        *self = Self::new();
+    }
+
+    /// Position the cursor, writes beyond the row end are clipped
+    pub fn set_cursor(&mut self, col: usize, row: usize) {
+        self.col = col;
+        self.row = row;
+    }
+
+    /// Write a character code at the cursor position
+    pub fn write_byte(&mut self, value: u8) {
+        if self.row < ROWS && self.col < COLUMNS {
+            self.cells[self.row][self.col] = value;
+        }
+        self.col += 1;
+    }
+
+    /// Move all rows up by one, the bottom row becomes blank
+    pub fn scroll_up(&mut self) {
+        self.cells.copy_within(1.., 0);
+        self.cells[ROWS - 1] = [b' '; COLUMNS];
+    }
+
+    /// Character codes of a row
+    pub fn row(&self, row: usize) -> &[u8; COLUMNS] {
+        &self.cells[row]
+    }
+
+    /// Transfer a row to the display
+    pub fn flush_row<E, I2C: Write<Error = E>>(
+        &self,
+        lcd: &RgbLCD,
+        i2c: &mut I2C,
+        row: usize,
+    ) -> Result<(), E> {
+        lcd.set_cursor(i2c, 0, row as u8)?;
+        lcd.write_bytes(i2c, &self.cells[row])
+    }
+
+    /// Transfer all rows to the display
+    pub fn flush<E, I2C: Write<Error = E>>(&self, lcd: &RgbLCD, i2c: &mut I2C) -> Result<(), E> {
+        for row in 0..ROWS {
+            self.flush_row(lcd, i2c, row)?;
+        }
+        Ok(())
+    }
+
+    /// Transfer the rows that differ from the frame shown before
+    pub fn flush_changes<E, I2C: Write<Error = E>>(
+        &self,
+        lcd: &RgbLCD,
+        i2c: &mut I2C,
+        shown: &Frame,
+    ) -> Result<(), E> {
+        for row in 0..ROWS {
+            if self.cells[row] != shown.cells[row] {
+                self.flush_row(lcd, i2c, row)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Default for Frame {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Text is mapped to the character ROM, missing characters become `?`
+impl fmt::Write for Frame {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        for c in s.chars() {
+            self.write_byte(charset::rom_code(c).unwrap_or(b'?'));
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,382 @@
+//!
+//! Priority based notifications on the backlight and a display row
+//!
+//! Subsystems post alerts with a priority, a text, an optional backlight
+//! pattern and a timeout. The highest priority active alert (the newest one
+//! among equal priorities) owns the backlight and overlays its text onto one
+//! row of the frame. Once it expires or is acknowledged the next alert takes
+//! over, or the backlight color and blinking set with `set_color` and
+//! `set_blink` are restored.
+//!
+//! ```ignore
+//! let mut slots = [None; 4];
+//! let mut alerts = AlertCenter::new(&mut slots, 1, Color::Green);
+//! alerts.post(Alert::error("I2C bus error").with_timeout(5000));
+//! // each tick
+//! alerts.tick(&lcd, &mut i2c)?;
+//! page.render(&mut frame);
+//! alerts.render(&mut frame);
+//! frame.flush_changes(&lcd, &mut i2c, &shown)?;
+//! ```
+
+use embedded_hal::blocking::i2c::Write;
+
+use super::layout::{Align, Field};
+use super::{Frame, COLUMNS, ROWS};
+use crate::lcd::{Color, RgbLCD};
+
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub enum Priority {
+    Info,
+    Warning,
+    Error,
+}
+
+/// Backlight presentation of an alert
+#[derive(Clone, Copy, PartialEq)]
+pub enum Pattern {
+    Steady(Color),
+    /// Hardware blinking, once a second
+    Blink(Color),
+    /// Alternate between two colors every period ticks
+    Alternate(Color, Color, u32),
+}
+
+#[derive(Clone, Copy)]
+pub struct Alert<'t> {
+    priority: Priority,
+    text: &'t str,
+    pattern: Option<Pattern>,
+    timeout: u32,
+    remaining: u32,
+    sequence: u32,
+}
+
+impl<'t> Alert<'t> {
+    /// Create an alert shown until acknowledged, without backlight pattern
+    pub fn new(priority: Priority, text: &'t str) -> Self {
+        Alert {
+            priority,
+            text,
+            pattern: None,
+            timeout: 0,
+            remaining: 0,
+            sequence: 0,
+        }
+    }
+
+    /// Informational alert with steady blue backlight
+    pub fn info(text: &'t str) -> Self {
+        Alert::new(Priority::Info, text).with_pattern(Pattern::Steady(Color::Blue))
+    }
+
+    /// Warning with steady yellow backlight
+    pub fn warning(text: &'t str) -> Self {
+        Alert::new(Priority::Warning, text).with_pattern(Pattern::Steady(Color::RGB(255, 160, 0)))
+    }
+
+    /// Error with blinking red backlight
+    pub fn error(text: &'t str) -> Self {
+        Alert::new(Priority::Error, text).with_pattern(Pattern::Blink(Color::Red))
+    }
+
+    /// Set the backlight pattern
+    pub fn with_pattern(mut self, pattern: Pattern) -> Self {
+        self.pattern = Some(pattern);
+        self
+    }
+
+    /// Leave the backlight as is
+    pub fn without_pattern(mut self) -> Self {
+        self.pattern = None;
+        self
+    }
+
+    /// Expire after that many ticks, 0 keeps the alert until acknowledged
+    pub fn with_timeout(mut self, ticks: u32) -> Self {
+        self.timeout = ticks;
+        self
+    }
+
+    pub fn priority(&self) -> Priority {
+        self.priority
+    }
+
+    pub fn text(&self) -> &'t str {
+        self.text
+    }
+}
+
+/// Handle of a posted alert
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct AlertId(usize, u32);
+
+pub struct AlertCenter<'a, 't> {
+    slots: &'a mut [Option<Alert<'t>>],
+    row: usize,
+    color: Color,
+    blink: bool,
+    sequence: u32,
+    shown: Option<AlertId>,
+    ticks: u32,
+    backlight_changed: bool,
+}
+
+impl<'a, 't> AlertCenter<'a, 't> {
+    /// Create an alert center
+    ///
+    /// Args:
+    /// * slots - storage, the number of alerts that can be active at once
+    /// * row - display row overlaid by the alert text
+    /// * color - backlight color while no alert is shown
+    pub fn new(slots: &'a mut [Option<Alert<'t>>], row: usize, color: Color) -> Self {
+        if slots.is_empty() {
+            panic!("Alert center requires at least one slot");
+        }
+        if row >= ROWS {
+            panic!("Alert row must be in range 0..1");
+        }
+        slots.iter_mut().for_each(|slot| *slot = None);
+        AlertCenter {
+            slots,
+            row,
+            color,
+            blink: false,
+            sequence: 0,
+            shown: None,
+            ticks: 0,
+            backlight_changed: true,
+        }
+    }
+
+    /// Set the backlight color restored once no alert is shown
+    pub fn set_color(&mut self, color: Color) {
+        self.color = color;
+        self.backlight_changed |= self.shown.is_none();
+    }
+
+    /// Set the backlight blinking restored once no alert is shown
+    pub fn set_blink(&mut self, blink: bool) {
+        self.blink = blink;
+        self.backlight_changed |= self.shown.is_none();
+    }
+
+    /// Post an alert
+    ///
+    /// If all slots are taken, the oldest alert of the lowest priority below
+    /// the posted one is replaced.
+    ///
+    /// Returns
+    /// * handle of the alert, None if it was dropped
+    pub fn post(&mut self, alert: Alert<'t>) -> Option<AlertId> {
+        let index = match self.slots.iter().position(|slot| slot.is_none()) {
+            Some(index) => index,
+            None => {
+                let (index, lowest) = self
+                    .slots
+                    .iter()
+                    .enumerate()
+                    .filter_map(|(index, slot)| slot.as_ref().map(|alert| (index, alert)))
+                    .min_by_key(|(_, alert)| (alert.priority, alert.sequence))?;
+                if lowest.priority >= alert.priority {
+                    return None;
+                }
+                index
+            }
+        };
+        self.sequence = self.sequence.wrapping_add(1);
+        self.slots[index] = Some(Alert {
+            remaining: alert.timeout,
+            sequence: self.sequence,
+            ..alert
+        });
+        Some(AlertId(index, self.sequence))
+    }
+
+    /// Remove a posted alert, does nothing if it is gone already
+    pub fn dismiss(&mut self, id: AlertId) {
+        if let Some(alert) = self.slots[id.0] {
+            if alert.sequence == id.1 {
+                self.slots[id.0] = None;
+            }
+        }
+    }
+
+    /// Remove the shown alert
+    pub fn acknowledge(&mut self) {
+        if let Some(id) = self.active_id() {
+            self.dismiss(id);
+        }
+    }
+
+    /// Alert owning the backlight and display row
+    pub fn active(&self) -> Option<&Alert<'t>> {
+        self.active_id().and_then(|id| self.slots[id.0].as_ref())
+    }
+
+    /// Expire alerts and update the backlight, to be called from a periodic tick
+    ///
+    /// Returns
+    /// * true if another alert (or none) is shown since the last tick
+    pub fn tick<E, I2C: Write<Error = E>>(
+        &mut self,
+        lcd: &RgbLCD,
+        i2c: &mut I2C,
+    ) -> Result<bool, E> {
+        for slot in self.slots.iter_mut() {
+            if let Some(alert) = slot {
+                if alert.timeout > 0 {
+                    alert.remaining -= 1;
+                    if alert.remaining == 0 {
+                        *slot = None;
+                    }
+                }
+            }
+        }
+        let active = self.active_id();
+        let changed = active != self.shown;
+        if changed {
+            self.shown = active;
+            self.ticks = 0;
+            self.backlight_changed = true;
+        } else {
+            self.ticks = self.ticks.wrapping_add(1);
+        }
+        let pattern = self.active().and_then(|alert| alert.pattern);
+        match pattern {
+            _ if !self.backlight_changed => {
+                if let Some(Pattern::Alternate(first, second, period)) = pattern {
+                    if period > 0 && self.ticks % period == 0 {
+                        let phase = (self.ticks / period) % 2;
+                        lcd.set_color(i2c, if phase == 0 { first } else { second })?;
+                    }
+                }
+            }
+            Some(Pattern::Steady(color)) | Some(Pattern::Alternate(color, _, _)) => {
+                lcd.switch_blink_backlight_off(i2c)?;
+                lcd.set_color(i2c, color)?;
+            }
+            Some(Pattern::Blink(color)) => {
+                lcd.switch_blink_backlight_on(i2c)?;
+                lcd.set_color(i2c, color)?;
+            }
+            None => {
+                if self.blink {
+                    lcd.switch_blink_backlight_on(i2c)?;
+                } else {
+                    lcd.switch_blink_backlight_off(i2c)?;
+                }
+                lcd.set_color(i2c, self.color)?;
+            }
+        }
+        self.backlight_changed = false;
+        Ok(changed)
+    }
+
+    /// Overlay the text of the shown alert onto its row
+    pub fn render(&self, frame: &mut Frame) {
+        if let Some(alert) = self.active() {
+            Field::new(0, self.row as u8, COLUMNS as u8, Align::Left).render_str(frame, alert.text);
+        }
+    }
+
+    fn active_id(&self) -> Option<AlertId> {
+        self.slots
+            .iter()
+            .enumerate()
+            .filter_map(|(index, slot)| slot.as_ref().map(|alert| (index, alert)))
+            .max_by_key(|(_, alert)| (alert.priority, alert.sequence))
+            .map(|(index, alert)| AlertId(index, alert.sequence))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::I2cMock;
+    use core::fmt::Write as _;
+
+    fn show(alerts: &mut AlertCenter, lcd: &RgbLCD, i2c: &mut I2cMock) -> bool {
+        let changed = alerts.tick(lcd, i2c).unwrap();
+        let mut frame = Frame::new();
+        write!(frame, "Page").unwrap();
+        frame.set_cursor(0, 1);
+        write!(frame, "status").unwrap();
+        alerts.render(&mut frame);
+        frame.flush(lcd, i2c).unwrap();
+        changed
+    }
+
+    #[test]
+    fn highest_priority_owns_backlight_and_row() {
+        let lcd = RgbLCD::new();
+        let mut i2c = I2cMock::new();
+        let mut slots = [None; 3];
+        let mut alerts = AlertCenter::new(&mut slots, 1, Color::Green);
+
+        show(&mut alerts, &lcd, &mut i2c);
+        assert_eq!(i2c.line(1), "status          ");
+        assert_eq!(i2c.color(), (0, 255, 0));
+
+        alerts.post(Alert::info("Paired").with_timeout(3));
+        let error = alerts.post(Alert::error("IR overrun")).unwrap();
+        assert!(show(&mut alerts, &lcd, &mut i2c));
+        assert_eq!(i2c.line(1), "IR overrun      ");
+        assert_eq!(i2c.color(), (255, 0, 0));
+        assert_eq!(i2c.registers[7], 0x17);
+
+        alerts.dismiss(error);
+        assert!(show(&mut alerts, &lcd, &mut i2c));
+        assert_eq!(i2c.line(1), "Paired          ");
+        assert_eq!(i2c.color(), (0, 0, 255));
+        assert_eq!(i2c.registers[7], 0x00);
+
+        // the info alert expires with its third tick
+        assert!(show(&mut alerts, &lcd, &mut i2c));
+        assert_eq!(i2c.line(1), "status          ");
+        assert_eq!(i2c.color(), (0, 255, 0));
+    }
+
+    #[test]
+    fn acknowledge_and_replace_when_full() {
+        let lcd = RgbLCD::new();
+        let mut i2c = I2cMock::new();
+        let mut slots = [None; 2];
+        let mut alerts = AlertCenter::new(&mut slots, 0, Color::White);
+
+        alerts.post(Alert::warning("first"));
+        alerts.post(Alert::info("second"));
+        assert!(alerts.post(Alert::info("dropped")).is_none());
+        assert!(alerts.post(Alert::error("replaces second")).is_some());
+        assert_eq!(alerts.active().unwrap().text(), "replaces second");
+
+        alerts.acknowledge();
+        assert_eq!(alerts.active().unwrap().text(), "first");
+        alerts.acknowledge();
+        assert!(alerts.active().is_none());
+        alerts.tick(&lcd, &mut i2c).unwrap();
+        assert_eq!(i2c.color(), (255, 255, 255));
+    }
+
+    #[test]
+    fn alternate_colors() {
+        let lcd = RgbLCD::new();
+        let mut i2c = I2cMock::new();
+        let mut slots = [None; 1];
+        let mut alerts = AlertCenter::new(&mut slots, 0, Color::White);
+        alerts.post(
+            Alert::new(Priority::Warning, "Battery").with_pattern(Pattern::Alternate(
+                Color::Red,
+                Color::Blue,
+                2,
+            )),
+        );
+        let mut colors = [(0, 0, 0); 5];
+        for color in colors.iter_mut() {
+            alerts.tick(&lcd, &mut i2c).unwrap();
+            *color = i2c.color();
+        }
+        let (red, blue) = ((255, 0, 0), (0, 0, 255));
+        assert_eq!(colors, [red, red, blue, blue, red]);
+    }
+}
@@ -0,0 +1,430 @@
+//!
+//! ANSI/VT100 terminal on the 16x2 display
+//!
+//! Interprets a byte stream with escape sequences, e.g. received by UART, and
+//! translates it into LCD commands. UTF-8 text is mapped to the character ROM.
+//!
+//! Supported control characters and sequences:
+//!
+//! * `\r` carriage return, `\n` new line (includes carriage return), `\x08` backspace;
+//!   writing beyond the bottom row scrolls the display up
+//! * `ESC[<row>;<col>H` and `ESC[<row>;<col>f` cursor position (1-based)
+//! * `ESC[<n>A`, `B`, `C`, `D` cursor up, down, forward, back
+//! * `ESC[<n>J` erase display, `ESC[<n>K` erase line (0: to end, 1: to cursor, 2: all)
+//! * `ESC[?25h`, `ESC[?25l` show and hide the cursor
+//! * `ESC[s`, `ESC[u`, `ESC7`, `ESC8` save and restore the cursor position, `ESCc` resets
+//! * `ESC[<n>m` colors (30-37, 40-47, 90-97, 100-107, 38;2;r;g;b, 48;2;r;g;b) set the
+//!   backlight color, 5 and 25 switch backlight blinking on and off, 0 resets
+//!
+//! Other sequences are ignored.
+
+use embedded_hal::blocking::i2c::Write;
+
+use super::{Frame, COLUMNS, ROWS};
+use crate::lcd::{charset, Color, RgbLCD};
+
+const ESC: u8 = 0x1b;
+const MAX_PARAMS: usize = 8;
+
+/// Backlight levels of normal and bright colors
+const NORMAL: u8 = 0xaa;
+const BRIGHT: u8 = 0xff;
+
+#[derive(Clone, Copy, PartialEq)]
+enum State {
+    Ground,
+    Escape,
+    Csi,
+    Utf8 { remaining: u8, scalar: u32 },
+}
+
+pub struct Terminal {
+    state: State,
+    params: [u16; MAX_PARAMS],
+    count: usize,
+    private: bool,
+    frame: Frame,
+    col: usize,
+    row: usize,
+    saved: (usize, usize),
+    synced: bool,
+    default_color: Color,
+}
+
+impl Terminal {
+    /// Create a terminal, default_color is the backlight color after reset (`ESC[0m`)
+    pub fn new(default_color: Color) -> Self {
+        Terminal {
+            state: State::Ground,
+            params: [0; MAX_PARAMS],
+            count: 0,
+            private: false,
+            frame: Frame::new(),
+            col: 0,
+            row: 0,
+            saved: (0, 0),
+            synced: false,
+            default_color,
+        }
+    }
+
+    /// Cursor position as (col, row)
+    pub fn cursor(&self) -> (usize, usize) {
+        (self.col, self.row)
+    }
+
+    /// Characters shown on the display
+    pub fn frame(&self) -> &Frame {
+        &self.frame
+    }
+
+    /// Interpret a sequence of bytes
+    pub fn write<E, I2C: Write<Error = E>>(
+        &mut self,
+        lcd: &mut RgbLCD,
+        i2c: &mut I2C,
+        bytes: &[u8],
+    ) -> Result<(), E> {
+        for byte in bytes {
+            self.feed(lcd, i2c, *byte)?;
+        }
+        Ok(())
+    }
+
+    /// Interpret a single byte
+    pub fn feed<E, I2C: Write<Error = E>>(
+        &mut self,
+        lcd: &mut RgbLCD,
+        i2c: &mut I2C,
+        byte: u8,
+    ) -> Result<(), E> {
+        match self.state {
+            State::Ground => self.ground(lcd, i2c, byte),
+            State::Escape => {
+                self.state = State::Ground;
+                match byte {
+                    b'[' => {
+                        self.params = [0; MAX_PARAMS];
+                        self.count = 0;
+                        self.private = false;
+                        self.state = State::Csi;
+                    }
+                    b'7' => self.saved = (self.col, self.row),
+                    b'8' => self.restore(),
+                    b'c' => {
+                        for row in 0..ROWS {
+                            self.erase(lcd, i2c, row, 0, COLUMNS)?;
+                        }
+                        self.move_to(0, 0);
+                        lcd.set_color(i2c, self.default_color)?;
+                    }
+                    _ => (),
+                }
+                Ok(())
+            }
+            State::Csi => match byte {
+                b'0'..=b'9' => {
+                    if self.count == 0 {
+                        self.count = 1;
+                    }
+                    if let Some(param) = self.params.get_mut(self.count - 1) {
+                        *param = param
+                            .saturating_mul(10)
+                            .saturating_add((byte - b'0') as u16);
+                    }
+                    Ok(())
+                }
+                b';' => {
+                    self.count = (self.count.max(1) + 1).min(MAX_PARAMS + 1);
+                    Ok(())
+                }
+                b'?' => {
+                    self.private = true;
+                    Ok(())
+                }
+                0x40..=0x7e => {
+                    self.state = State::Ground;
+                    self.dispatch(lcd, i2c, byte)
+                }
+                _ => Ok(()), // intermediate bytes are ignored
+            },
+            State::Utf8 { remaining, scalar } => {
+                if byte & 0xc0 != 0x80 {
+                    // malformed sequence
+                    self.state = State::Ground;
+                    self.put(lcd, i2c, b'?')?;
+                    return self.ground(lcd, i2c, byte);
+                }
+                let scalar = (scalar << 6) | (byte & 0x3f) as u32;
+                if remaining > 1 {
+                    self.state = State::Utf8 {
+                        remaining: remaining - 1,
+                        scalar,
+                    };
+                    return Ok(());
+                }
+                self.state = State::Ground;
+                let code = core::char::from_u32(scalar)
+                    .and_then(charset::rom_code)
+                    .unwrap_or(b'?');
+                self.put(lcd, i2c, code)
+            }
+        }
+    }
+
+    fn ground<E, I2C: Write<Error = E>>(
+        &mut self,
+        lcd: &mut RgbLCD,
+        i2c: &mut I2C,
+        byte: u8,
+    ) -> Result<(), E> {
+        match byte {
+            ESC => self.state = State::Escape,
+            b'\r' => self.move_to(0, self.row),
+            b'\n' => self.new_line(lcd, i2c)?,
+            0x08 => self.move_to(self.col.saturating_sub(1), self.row),
+            0x20..=0x7e => {
+                let code = charset::rom_code(byte as char).unwrap_or(b'?');
+                self.put(lcd, i2c, code)?;
+            }
+            0xc0..=0xdf => {
+                self.state = State::Utf8 {
+                    remaining: 1,
+                    scalar: (byte & 0x1f) as u32,
+                }
+            }
+            0xe0..=0xef => {
+                self.state = State::Utf8 {
+                    remaining: 2,
+                    scalar: (byte & 0x0f) as u32,
+                }
+            }
+            0xf0..=0xf7 => {
+                self.state = State::Utf8 {
+                    remaining: 3,
+                    scalar: (byte & 0x07) as u32,
+                }
+            }
+            _ => (), // other control characters
+        }
+        Ok(())
+    }
+
+    fn dispatch<E, I2C: Write<Error = E>>(
+        &mut self,
+        lcd: &mut RgbLCD,
+        i2c: &mut I2C,
+        command: u8,
+    ) -> Result<(), E> {
+        let count = self.count.min(MAX_PARAMS);
+        let params = self.params;
+        let param = |index: usize, default: u16| match params[index] {
+            0 => default,
+            value => value,
+        };
+        match command {
+            b'H' | b'f' => {
+                let row = param(0, 1) as usize - 1;
+                let col = param(1, 1) as usize - 1;
+                self.move_to(col, row);
+            }
+            b'A' => self.move_to(self.col, self.row.saturating_sub(param(0, 1) as usize)),
+            b'B' => self.move_to(self.col, self.row + param(0, 1) as usize),
+            b'C' => self.move_to(self.col + param(0, 1) as usize, self.row),
+            b'D' => self.move_to(self.col.saturating_sub(param(0, 1) as usize), self.row),
+            b'J' => match self.params[0] {
+                0 => {
+                    self.erase(lcd, i2c, self.row, self.col, COLUMNS)?;
+                    for row in self.row + 1..ROWS {
+                        self.erase(lcd, i2c, row, 0, COLUMNS)?;
+                    }
+                }
+                1 => {
+                    for row in 0..self.row {
+                        self.erase(lcd, i2c, row, 0, COLUMNS)?;
+                    }
+                    self.erase(lcd, i2c, self.row, 0, self.col + 1)?;
+                }
+                _ => {
+                    for row in 0..ROWS {
+                        self.erase(lcd, i2c, row, 0, COLUMNS)?;
+                    }
+                }
+            },
+            b'K' => match self.params[0] {
+                0 => self.erase(lcd, i2c, self.row, self.col, COLUMNS)?,
+                1 => self.erase(lcd, i2c, self.row, 0, self.col + 1)?,
+                _ => self.erase(lcd, i2c, self.row, 0, COLUMNS)?,
+            },
+            b'h' if self.private && self.params[0] == 25 => lcd.show_cursor(i2c)?,
+            b'l' if self.private && self.params[0] == 25 => lcd.hide_cursor(i2c)?,
+            b's' => self.saved = (self.col, self.row),
+            b'u' => self.restore(),
+            b'm' => self.select_graphic_rendition(lcd, i2c, count)?,
+            _ => (),
+        }
+        Ok(())
+    }
+
+    fn select_graphic_rendition<E, I2C: Write<Error = E>>(
+        &mut self,
+        lcd: &mut RgbLCD,
+        i2c: &mut I2C,
+        count: usize,
+    ) -> Result<(), E> {
+        let mut index = 0;
+        // no parameter is a reset
+        let count = count.max(1);
+        while index < count {
+            match self.params[index] {
+                0 => {
+                    lcd.switch_blink_backlight_off(i2c)?;
+                    lcd.set_color(i2c, self.default_color)?;
+                }
+                5 => lcd.switch_blink_backlight_on(i2c)?,
+                25 => lcd.switch_blink_backlight_off(i2c)?,
+                code @ 30..=37 | code @ 40..=47 => {
+                    lcd.set_color(i2c, ansi_color(code % 10, NORMAL))?
+                }
+                code @ 90..=97 | code @ 100..=107 => {
+                    lcd.set_color(i2c, ansi_color(code % 10, BRIGHT))?
+                }
+                38 | 48 if index + 4 < count && self.params[index + 1] == 2 => {
+                    let level = |i: usize| self.params[index + i].min(255) as u8;
+                    lcd.set_color(i2c, Color::RGB(level(2), level(3), level(4)))?;
+                    index += 4;
+                }
+                39 | 49 => lcd.set_color(i2c, self.default_color)?,
+                _ => (),
+            }
+            index += 1;
+        }
+        Ok(())
+    }
+
+    fn put<E, I2C: Write<Error = E>>(
+        &mut self,
+        lcd: &mut RgbLCD,
+        i2c: &mut I2C,
+        code: u8,
+    ) -> Result<(), E> {
+        if self.col >= COLUMNS {
+            self.new_line(lcd, i2c)?;
+        }
+        if !self.synced {
+            lcd.set_cursor(i2c, self.col as u8, self.row as u8)?;
+            self.synced = true;
+        }
+        lcd.write_byte(i2c, code)?;
+        self.frame.set_cursor(self.col, self.row);
+        self.frame.write_byte(code);
+        self.col += 1;
+        // the address counter leaves the visible area at the row end
+        self.synced = self.col < COLUMNS;
+        Ok(())
+    }
+
+    fn new_line<E, I2C: Write<Error = E>>(
+        &mut self,
+        lcd: &mut RgbLCD,
+        i2c: &mut I2C,
+    ) -> Result<(), E> {
+        if self.row + 1 < ROWS {
+            self.move_to(0, self.row + 1);
+        } else {
+            self.frame.scroll_up();
+            self.frame.flush(lcd, i2c)?;
+            self.move_to(0, ROWS - 1);
+        }
+        Ok(())
+    }
+
+    fn erase<E, I2C: Write<Error = E>>(
+        &mut self,
+        lcd: &RgbLCD,
+        i2c: &mut I2C,
+        row: usize,
+        from: usize,
+        to: usize,
+    ) -> Result<(), E> {
+        let to = to.min(COLUMNS);
+        if from >= to {
+            return Ok(());
+        }
+        self.frame.set_cursor(from, row);
+        for _ in from..to {
+            self.frame.write_byte(b' ');
+        }
+        lcd.set_cursor(i2c, from as u8, row as u8)?;
+        lcd.write_bytes(i2c, &[b' '; COLUMNS][from..to])?;
+        self.synced = false;
+        Ok(())
+    }
+
+    fn move_to(&mut self, col: usize, row: usize) {
+        self.col = col.min(COLUMNS - 1);
+        self.row = row.min(ROWS - 1);
+        self.synced = false;
+    }
+
+    fn restore(&mut self) {
+        let (col, row) = self.saved;
+        self.move_to(col, row);
+    }
+}
+
+fn ansi_color(code: u16, level: u8) -> Color {
+    // bit 0: red, bit 1: green, bit 2: blue
+    let on = |bit: u16| if code & bit != 0 { level } else { 0 };
+    Color::RGB(on(1), on(2), on(4))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::I2cMock;
+
+    fn run(bytes: &[u8]) -> (Terminal, I2cMock) {
+        let mut lcd = RgbLCD::new();
+        let mut i2c = I2cMock::new();
+        let mut terminal = Terminal::new(Color::White);
+        terminal.write(&mut lcd, &mut i2c, bytes).unwrap();
+        (terminal, i2c)
+    }
+
+    #[test]
+    fn text_wraps_and_scrolls() {
+        let (terminal, i2c) = run(b"first\nsecond\nthird 25\xc2\xb0C");
+        assert_eq!(i2c.line(0), "second          ");
+        assert_eq!(&i2c.line(1)[..8], "third 25");
+        assert_eq!(i2c.ddram[0x48], 0xdf);
+        assert_eq!(terminal.cursor(), (10, 1));
+    }
+
+    #[test]
+    fn cursor_position_and_erase() {
+        let (_, i2c) = run(b"0123456789\x1b[2;3Hab\x1b[1;5H\x1b[K\x1b[2;1H\x1b[1K");
+        assert_eq!(i2c.line(0), "0123            ");
+        assert_eq!(i2c.line(1), "  ab            ");
+    }
+
+    #[test]
+    fn save_restore_and_cursor_visibility() {
+        let (terminal, i2c) = run(b"\x1b[2;4H\x1b[s\x1b[1;1Hx\x1b[u\x1b[?25hy");
+        assert_eq!(i2c.line(1), "   y            ");
+        assert_eq!(terminal.cursor(), (4, 1));
+        assert_eq!(i2c.display_control & 0x02, 0x02);
+    }
+
+    #[test]
+    fn colors_set_backlight() {
+        let (_, i2c) = run(b"\x1b[41m");
+        assert_eq!(i2c.color(), (0xaa, 0, 0));
+        let (_, i2c) = run(b"\x1b[1;96m");
+        assert_eq!(i2c.color(), (0, 0xff, 0xff));
+        let (_, i2c) = run(b"\x1b[38;2;1;2;3m");
+        assert_eq!(i2c.color(), (1, 2, 3));
+        let (_, i2c) = run(b"\x1b[34m\x1b[m");
+        assert_eq!(i2c.color(), (255, 255, 255));
+    }
+}
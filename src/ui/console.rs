@@ -0,0 +1,252 @@
+//!
+//! Scrolling log console
+//!
+//! Text written to the console is appended to an in-RAM line history. The
+//! display shows the two newest lines, each new line pushes the previous one
+//! up. Older lines of the history can be paged with navigation events.
+//!
+//! ```ignore
+//! writeln!(console, "IR {:04x}", datagram).ok();
+//! console.draw(&lcd, &mut i2c)?;
+//! ```
+
+use core::fmt;
+
+use embedded_hal::blocking::i2c::Write;
+
+use super::screen::Page;
+use super::{Event, Frame, COLUMNS, ROWS};
+use crate::lcd::{charset, RgbLCD};
+
+pub struct Console<'a> {
+    lines: &'a mut [[u8; COLUMNS]],
+    newest: usize,
+    count: usize,
+    col: usize,
+    pending_new_line: bool,
+    offset: usize,
+    shown: Option<Frame>,
+}
+
+impl<'a> Console<'a> {
+    /// Create a console keeping the last `history.len()` lines
+    pub fn new(history: &'a mut [[u8; COLUMNS]]) -> Self {
+        if history.len() < ROWS {
+            panic!("Console history requires at least two lines");
+        }
+        history[0] = [b' '; COLUMNS];
+        Console {
+            lines: history,
+            newest: 0,
+            count: 1,
+            col: 0,
+            pending_new_line: false,
+            offset: 0,
+            shown: None,
+        }
+    }
+
+    /// Number of lines the view is scrolled back from the newest line
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    /// Append a character code, `\n` ends the line
+    pub fn write_byte(&mut self, code: u8) {
+        if code == b'\n' {
+            if self.pending_new_line {
+                self.new_line();
+            }
+            self.pending_new_line = true;
+            return;
+        }
+        if self.pending_new_line || self.col == COLUMNS {
+            self.new_line();
+        }
+        self.lines[self.newest][self.col] = code;
+        self.col += 1;
+    }
+
+    /// Scroll back towards older lines
+    pub fn scroll_back(&mut self, lines: usize) {
+        self.offset = (self.offset + lines).min(self.max_offset());
+    }
+
+    /// Scroll forward towards the newest line
+    pub fn scroll_forward(&mut self, lines: usize) {
+        self.offset = self.offset.saturating_sub(lines);
+    }
+
+    /// Show the newest lines again
+    pub fn follow(&mut self) {
+        self.offset = 0;
+    }
+
+    /// Page with Up/Left (back) and Down/Right (forward), Ok/Back return to the newest lines
+    ///
+    /// Returns
+    /// * true if the view changed
+    pub fn handle(&mut self, event: Event) -> bool {
+        let offset = self.offset;
+        match event {
+            Event::Up | Event::Left => self.scroll_back(1),
+            Event::Down | Event::Right => self.scroll_forward(1),
+            Event::Ok | Event::Back => self.follow(),
+        }
+        offset != self.offset
+    }
+
+    /// Transfer the rows that changed since the last draw
+    pub fn draw<E, I2C: Write<Error = E>>(&mut self, lcd: &RgbLCD, i2c: &mut I2C) -> Result<(), E> {
+        let mut frame = Frame::new();
+        self.render(&mut frame);
+        match self.shown {
+            Some(ref shown) => frame.flush_changes(lcd, i2c, shown)?,
+            None => frame.flush(lcd, i2c)?,
+        }
+        self.shown = Some(frame);
+        Ok(())
+    }
+
+    /// Redraw all rows with the next draw, e.g. if the display was used otherwise
+    pub fn invalidate(&mut self) {
+        self.shown = None;
+    }
+
+    fn new_line(&mut self) {
+        self.newest = (self.newest + 1) % self.lines.len();
+        self.lines[self.newest] = [b' '; COLUMNS];
+        self.count = (self.count + 1).min(self.lines.len());
+        self.col = 0;
+        self.pending_new_line = false;
+        if self.offset > 0 {
+            // keep the view on the lines shown
+            self.scroll_back(1);
+        }
+    }
+
+    fn max_offset(&self) -> usize {
+        self.count.saturating_sub(ROWS)
+    }
+
+    /// Line by age, 0 is the newest line
+    fn line(&self, age: usize) -> Option<&[u8; COLUMNS]> {
+        if age >= self.count {
+            return None;
+        }
+        let len = self.lines.len();
+        Some(&self.lines[(self.newest + len - age) % len])
+    }
+}
+
+impl<'a> Page for Console<'a> {
+    fn render(&mut self, frame: &mut Frame) {
+        // the newest line is shown at the bottom, unless there is just one line
+        let bottom_age = self.offset;
+        let top_age = if self.count < ROWS {
+            0
+        } else {
+            bottom_age + ROWS - 1
+        };
+        for row in 0..ROWS {
+            let age = top_age.checked_sub(row);
+            if let Some(line) = age.and_then(|age| self.line(age)) {
+                frame.set_cursor(0, row);
+                line.iter().for_each(|code| frame.write_byte(*code));
+            }
+        }
+    }
+}
+
+/// Text is mapped to the character ROM, missing characters become `?`
+impl<'a> fmt::Write for Console<'a> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        for c in s.chars() {
+            match c {
+                '\n' => self.write_byte(b'\n'),
+                '\r' => (),
+                _ => self.write_byte(charset::rom_code(c).unwrap_or(b'?')),
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::I2cMock;
+    use core::fmt::Write as _;
+
+    #[test]
+    fn new_lines_push_previous_up() {
+        let mut history = [[0; COLUMNS]; 4];
+        let mut console = Console::new(&mut history);
+        let lcd = RgbLCD::new();
+        let mut i2c = I2cMock::new();
+
+        writeln!(console, "first").unwrap();
+        console.draw(&lcd, &mut i2c).unwrap();
+        assert_eq!(i2c.line(0), "first           ");
+
+        writeln!(console, "second").unwrap();
+        console.draw(&lcd, &mut i2c).unwrap();
+        assert_eq!(i2c.line(0), "first           ");
+        assert_eq!(i2c.line(1), "second          ");
+
+        write!(console, "0123456789abcdefwrap").unwrap();
+        console.draw(&lcd, &mut i2c).unwrap();
+        assert_eq!(i2c.line(0), "0123456789abcdef");
+        assert_eq!(i2c.line(1), "wrap            ");
+    }
+
+    #[test]
+    fn page_through_history() {
+        let mut history = [[0; COLUMNS]; 3];
+        let mut console = Console::new(&mut history);
+        let lcd = RgbLCD::new();
+        let mut i2c = I2cMock::new();
+        for line in ["a", "b", "c", "d"].iter() {
+            writeln!(console, "{}", line).unwrap();
+        }
+        // "a" was dropped from the history
+        assert!(console.handle(Event::Up));
+        assert!(!console.handle(Event::Up));
+        console.draw(&lcd, &mut i2c).unwrap();
+        assert_eq!(&i2c.line(0)[..1], "b");
+        assert_eq!(&i2c.line(1)[..1], "c");
+
+        // the history is full, the view moves on as "b" is dropped
+        writeln!(console, "e").unwrap();
+        console.draw(&lcd, &mut i2c).unwrap();
+        assert_eq!(&i2c.line(0)[..1], "c");
+        assert_eq!(&i2c.line(1)[..1], "d");
+
+        assert!(console.handle(Event::Ok));
+        console.draw(&lcd, &mut i2c).unwrap();
+        assert_eq!(&i2c.line(0)[..1], "d");
+        assert_eq!(&i2c.line(1)[..1], "e");
+    }
+
+    #[test]
+    fn view_stays_while_scrolled_back() {
+        let mut history = [[0; COLUMNS]; 5];
+        let mut console = Console::new(&mut history);
+        let lcd = RgbLCD::new();
+        let mut i2c = I2cMock::new();
+        for line in ["a", "b", "c", "d"].iter() {
+            writeln!(console, "{}", line).unwrap();
+        }
+        assert!(console.handle(Event::Up));
+        console.draw(&lcd, &mut i2c).unwrap();
+        assert_eq!(&i2c.line(0)[..1], "b");
+        assert_eq!(&i2c.line(1)[..1], "c");
+
+        // new lines do not move the view while the history has room
+        writeln!(console, "e").unwrap();
+        console.draw(&lcd, &mut i2c).unwrap();
+        assert_eq!(console.offset(), 2);
+        assert_eq!(&i2c.line(0)[..1], "b");
+        assert_eq!(&i2c.line(1)[..1], "c");
+    }
+}
@@ -0,0 +1,81 @@
+//!
+//! Rotary encoder with push button as source of navigation events
+//!
+//! Turning clockwise yields `Event::Right`, counter clockwise `Event::Left`.
+//! A short push yields `Event::Ok`, holding the button yields `Event::Back`.
+
+use embedded_hal::digital::v2::InputPin;
+use rotary_encoder_hal::{Direction, Rotary};
+
+use super::Event;
+
+pub struct EncoderInput<A, B, P> {
+    rotary: Rotary<A, B>,
+    button: P,
+    steps_per_detent: i8,
+    steps: i8,
+    long_press_ticks: u32,
+    pressed_ticks: u32,
+    debounced: bool,
+}
+
+impl<A: InputPin, B: InputPin, P: InputPin> EncoderInput<A, B, P> {
+    /// Create encoder input
+    ///
+    /// Args:
+    /// * pin_a, pin_b - encoder channels, pulled up
+    /// * button - push button, active low
+    /// * steps_per_detent - encoder state changes per mechanical detent
+    /// * long_press_ticks - number of `poll` calls the button is held for `Event::Back`
+    pub fn new(pin_a: A, pin_b: B, button: P, steps_per_detent: i8, long_press_ticks: u32) -> Self {
+        EncoderInput {
+            rotary: Rotary::new(pin_a, pin_b),
+            button,
+            steps_per_detent: steps_per_detent.max(1),
+            steps: 0,
+            long_press_ticks,
+            pressed_ticks: 0,
+            debounced: false,
+        }
+    }
+
+    /// Sample the pins, to be called periodically (e.g. every millisecond)
+    ///
+    /// Pin read errors are treated as no change.
+    pub fn poll(&mut self) -> Option<Event> {
+        match self.rotary.update() {
+            Ok(Direction::Clockwise) => self.steps += 1,
+            Ok(Direction::CounterClockwise) => self.steps -= 1,
+            _ => (),
+        }
+        if self.steps >= self.steps_per_detent {
+            self.steps = 0;
+            return Some(Event::Right);
+        }
+        if self.steps <= -self.steps_per_detent {
+            self.steps = 0;
+            return Some(Event::Left);
+        }
+
+        let pressed = self.button.is_low().unwrap_or(false);
+        if pressed {
+            self.pressed_ticks = self.pressed_ticks.saturating_add(1);
+            // require two consecutive samples to suppress contact bounce
+            if self.pressed_ticks == 2 {
+                self.debounced = true;
+            }
+            if self.debounced && self.pressed_ticks == self.long_press_ticks {
+                self.debounced = false; // no Ok on release
+                return Some(Event::Back);
+            }
+            None
+        } else {
+            self.pressed_ticks = 0;
+            if self.debounced {
+                self.debounced = false;
+                return Some(Event::Ok);
+            }
+            None
+        }
+    }
+}
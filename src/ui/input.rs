@@ -0,0 +1,352 @@
+//!
+//! In place text and number entry
+//!
+//! The field is edited at its position on the display, the edited character
+//! is marked by the blinking LCD cursor:
+//!
+//! * Up/Down cycle the character at the cursor through the character set
+//! * Left/Right move the cursor
+//! * Ok confirms, Back cancels the edit
+//!
+//! Call `begin` to draw the field and switch the cursor on, `draw` after each
+//! handled event and `end` once the edit is confirmed or cancelled.
+
+use embedded_hal::blocking::i2c::Write;
+
+use super::{Event, COLUMNS};
+use crate::lcd::RgbLCD;
+
+const DIGITS: &[u8] = b"0123456789";
+const HEX_DIGITS: &[u8] = b"0123456789ABCDEF";
+const ALPHANUMERIC: &[u8] = b" ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789.-";
+const SIGN: &[u8] = b"+-";
+
+/// Characters selectable in an input field
+#[derive(Clone, Copy)]
+pub enum CharSet<'a> {
+    Digits,
+    Hex,
+    Alphanumeric,
+    /// ASCII characters available in the character ROM
+    Custom(&'a [u8]),
+}
+
+impl<'a> CharSet<'a> {
+    fn chars(&self) -> &'a [u8] {
+        match *self {
+            CharSet::Digits => DIGITS,
+            CharSet::Hex => HEX_DIGITS,
+            CharSet::Alphanumeric => ALPHANUMERIC,
+            CharSet::Custom(chars) => chars,
+        }
+    }
+}
+
+/// State of an edit after handling an event
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum EditResult<T> {
+    Editing,
+    Confirmed(T),
+    Cancelled,
+}
+
+pub struct TextInput<'a> {
+    text: [u8; COLUMNS],
+    backup: [u8; COLUMNS],
+    width: usize,
+    cursor: usize,
+    charset: &'a [u8],
+    signed: bool,
+    col: u8,
+    row: u8,
+}
+
+impl<'a> TextInput<'a> {
+    /// Create a text field
+    ///
+    /// Args:
+    /// * initial - initial text, characters not in charset become its first character
+    /// * width - number of characters, at most 16
+    /// * charset - selectable characters
+    /// * col, row - position on the display
+    pub fn new(initial: &str, width: usize, charset: CharSet<'a>, col: u8, row: u8) -> Self {
+        if width == 0 || width > COLUMNS.saturating_sub(col as usize) {
+            panic!("Input field must fit into the row");
+        }
+        let charset = charset.chars();
+        if charset.is_empty() {
+            panic!("Character set must not be empty");
+        }
+        let mut text = [charset[0]; COLUMNS];
+        for (cell, c) in text.iter_mut().zip(initial.bytes().take(width)) {
+            if charset.contains(&c) {
+                *cell = c;
+            }
+        }
+        TextInput {
+            text,
+            backup: text,
+            width,
+            cursor: 0,
+            charset,
+            signed: false,
+            col,
+            row,
+        }
+    }
+
+    /// Edited text
+    pub fn text(&self) -> &str {
+        // all characters are taken from ASCII character sets
+        core::str::from_utf8(&self.text[..self.width]).unwrap_or("")
+    }
+
+    /// Position of the cursor within the field
+    pub fn cursor(&self) -> usize {
+        self.cursor
+    }
+
+    /// Process an input event
+    pub fn handle(&mut self, event: Event) -> EditResult<&str> {
+        match event {
+            Event::Up => self.cycle(true),
+            Event::Down => self.cycle(false),
+            Event::Left => self.cursor = self.cursor.saturating_sub(1),
+            Event::Right => {
+                if self.cursor + 1 < self.width {
+                    self.cursor += 1;
+                }
+            }
+            Event::Ok => {
+                self.backup = self.text;
+                return EditResult::Confirmed(self.text());
+            }
+            Event::Back => {
+                self.text = self.backup;
+                return EditResult::Cancelled;
+            }
+        }
+        EditResult::Editing
+    }
+
+    /// Draw the field and switch the blinking cursor on
+    pub fn begin<E, I2C: Write<Error = E>>(
+        &self,
+        lcd: &mut RgbLCD,
+        i2c: &mut I2C,
+    ) -> Result<(), E> {
+        self.draw(lcd, i2c)?;
+        lcd.show_cursor(i2c)?;
+        lcd.switch_cursor_blinking_on(i2c)
+    }
+
+    /// Draw the field and place the cursor
+    pub fn draw<E, I2C: Write<Error = E>>(&self, lcd: &RgbLCD, i2c: &mut I2C) -> Result<(), E> {
+        lcd.set_cursor(i2c, self.col, self.row)?;
+        lcd.write_bytes(i2c, &self.text[..self.width])?;
+        lcd.set_cursor(i2c, self.col + self.cursor as u8, self.row)
+    }
+
+    /// Switch the cursor off
+    pub fn end<E, I2C: Write<Error = E>>(&self, lcd: &mut RgbLCD, i2c: &mut I2C) -> Result<(), E> {
+        lcd.switch_cursor_blinking_off(i2c)?;
+        lcd.hide_cursor(i2c)
+    }
+
+    fn charset_at(&self, position: usize) -> &'a [u8] {
+        if self.signed && position == 0 {
+            SIGN
+        } else {
+            self.charset
+        }
+    }
+
+    fn cycle(&mut self, up: bool) {
+        let charset = self.charset_at(self.cursor);
+        let count = charset.len();
+        let index = charset
+            .iter()
+            .position(|c| *c == self.text[self.cursor])
+            .unwrap_or(0);
+        let index = if up {
+            (index + 1) % count
+        } else {
+            (index + count - 1) % count
+        };
+        self.text[self.cursor] = charset[index];
+    }
+}
+
+/// Decimal or hexadecimal number entry within a range
+///
+/// A confirmed number out of range is replaced by the closest limit and
+/// stays in edit for a second confirmation.
+pub struct NumberInput {
+    field: TextInput<'static>,
+    radix: u32,
+    min: i32,
+    max: i32,
+}
+
+impl NumberInput {
+    /// Create a number field
+    ///
+    /// The width is given by the digits of min and max, a sign is shown if min is negative.
+    pub fn new(value: i32, min: i32, max: i32, radix: u32, col: u8, row: u8) -> Self {
+        let charset = match radix {
+            10 => CharSet::Digits,
+            16 => CharSet::Hex,
+            _ => panic!("Radix must be 10 or 16"),
+        };
+        if min > max {
+            panic!("Min must not exceed max");
+        }
+        let digits =
+            digit_count(min.unsigned_abs(), radix).max(digit_count(max.unsigned_abs(), radix));
+        let signed = min < 0;
+        let width = digits + signed as usize;
+        let mut input = NumberInput {
+            field: TextInput::new("", width, charset, col, row),
+            radix,
+            min,
+            max,
+        };
+        input.field.signed = signed;
+        input.set(value.max(min).min(max));
+        input.field.backup = input.field.text;
+        input
+    }
+
+    /// Number currently shown
+    pub fn value(&self) -> i32 {
+        let text = &self.field.text[..self.field.width];
+        let (negative, digits) = match self.field.signed {
+            true => (text[0] == b'-', &text[1..]),
+            false => (false, text),
+        };
+        let magnitude = digits.iter().fold(0i64, |value, c| {
+            value * self.radix as i64 + (*c as char).to_digit(self.radix).unwrap_or(0) as i64
+        });
+        let value = if negative { -magnitude } else { magnitude };
+        value.max(i32::MIN as i64).min(i32::MAX as i64) as i32
+    }
+
+    /// Process an input event
+    pub fn handle(&mut self, event: Event) -> EditResult<i32> {
+        match self.field.handle(event) {
+            EditResult::Editing => EditResult::Editing,
+            EditResult::Cancelled => EditResult::Cancelled,
+            EditResult::Confirmed(_) => {
+                let value = self.value();
+                if value < self.min || value > self.max {
+                    self.set(value.max(self.min).min(self.max));
+                    EditResult::Editing
+                } else {
+                    EditResult::Confirmed(value)
+                }
+            }
+        }
+    }
+
+    /// Access the underlying field, e.g. for drawing
+    pub fn field(&self) -> &TextInput<'static> {
+        &self.field
+    }
+
+    fn set(&mut self, value: i32) {
+        let width = self.field.width;
+        let mut magnitude = value.unsigned_abs();
+        let first = self.field.signed as usize;
+        for position in (first..width).rev() {
+            self.field.text[position] = HEX_DIGITS[(magnitude % self.radix) as usize];
+            magnitude /= self.radix;
+        }
+        if self.field.signed {
+            self.field.text[0] = if value < 0 { b'-' } else { b'+' };
+        }
+    }
+}
+
+fn digit_count(value: u32, radix: u32) -> usize {
+    let mut count = 1;
+    let mut value = value / radix;
+    while value > 0 {
+        count += 1;
+        value /= radix;
+    }
+    count
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::I2cMock;
+
+    #[test]
+    fn edit_text_in_place() {
+        let mut input = TextInput::new("AB", 4, CharSet::Alphanumeric, 2, 1);
+        assert_eq!(input.text(), "AB  ");
+        input.handle(Event::Right);
+        input.handle(Event::Up);
+        input.handle(Event::Left);
+        input.handle(Event::Left);
+        input.handle(Event::Down);
+        assert_eq!(input.handle(Event::Ok), EditResult::Confirmed(" C  "));
+
+        input.handle(Event::Up);
+        assert_eq!(input.handle(Event::Back), EditResult::Cancelled);
+        assert_eq!(input.text(), " C  ");
+    }
+
+    #[test]
+    fn draw_places_blinking_cursor() {
+        let mut lcd = RgbLCD::new();
+        let mut i2c = I2cMock::new();
+        let mut input = TextInput::new("1F", 2, CharSet::Hex, 3, 1);
+        input.begin(&mut lcd, &mut i2c).unwrap();
+        assert_eq!(i2c.display_control & 0x03, 0x03);
+        input.handle(Event::Right);
+        input.handle(Event::Up);
+        input.draw(&lcd, &mut i2c).unwrap();
+        assert_eq!(&i2c.line(1)[3..5], "10");
+        assert_eq!(i2c.cursor(), (4, 1));
+        input.end(&mut lcd, &mut i2c).unwrap();
+        assert_eq!(i2c.display_control & 0x03, 0x00);
+    }
+
+    #[test]
+    fn number_is_limited_to_range() {
+        let mut input = NumberInput::new(25, 0, 31, 10, 0, 0);
+        assert_eq!(input.field().text(), "25");
+        input.handle(Event::Up); // 35
+        assert_eq!(input.handle(Event::Ok), EditResult::Editing);
+        assert_eq!(input.field().text(), "31");
+        assert_eq!(input.handle(Event::Ok), EditResult::Confirmed(31));
+    }
+
+    #[test]
+    fn signed_and_hex_numbers() {
+        let mut input = NumberInput::new(-5, -40, 85, 10, 0, 0);
+        assert_eq!(input.field().text(), "-05");
+        input.handle(Event::Up);
+        assert_eq!(input.handle(Event::Ok), EditResult::Confirmed(5));
+
+        let mut input = NumberInput::new(0x1a, 0, 0xff, 16, 0, 0);
+        assert_eq!(input.field().text(), "1A");
+        input.handle(Event::Right);
+        input.handle(Event::Down);
+        assert_eq!(input.handle(Event::Ok), EditResult::Confirmed(0x19));
+    }
+
+    #[test]
+    #[should_panic(expected = "must fit")]
+    fn field_beyond_the_row_is_rejected() {
+        TextInput::new("", 1, CharSet::Digits, 17, 0);
+    }
+
+    #[test]
+    #[should_panic(expected = "must not be empty")]
+    fn empty_charset_is_rejected() {
+        TextInput::new("", 4, CharSet::Custom(&[]), 0, 0);
+    }
+}
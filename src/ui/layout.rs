@@ -0,0 +1,298 @@
+//!
+//! Placement of formatted values into fixed fields, without allocation
+//!
+//! A `Field` is a fixed area of a row. Formatted text is aligned within the
+//! field and truncated if too long, optionally ending with an ellipsis glyph
+//! (e.g. `charset::missing_glyph('…')` loaded into a CGRAM slot). Drawing a
+//! field only transfers its own characters, so a single value can be updated
+//! without redrawing the screen.
+//!
+//! ```ignore
+//! const TEMPERATURE: Field = Field::new(10, 0, 6, Align::Right);
+//! TEMPERATURE.draw_fmt(&lcd, &mut i2c, format_args!("{}°C", Decimal::new(-52, 1)))?;
+//! ```
+
+use core::fmt;
+
+use embedded_hal::blocking::i2c::Write;
+
+use super::Frame;
+use crate::lcd::{charset, RgbLCD, CANVAS_COLUMNS};
+
+/// Maximum number of characters of formatted text
+pub const TEXT_CAPACITY: usize = CANVAS_COLUMNS as usize;
+
+/// Formatted text as character codes
+///
+/// Text beyond the capacity is dropped and marks the text as truncated.
+#[derive(Clone, Copy)]
+pub struct Text {
+    buf: [u8; TEXT_CAPACITY],
+    len: usize,
+    truncated: bool,
+}
+
+impl Text {
+    pub const fn new() -> Self {
+        Text {
+            buf: [b' '; TEXT_CAPACITY],
+            len: 0,
+            truncated: false,
+        }
+    }
+
+    /// Format arguments, e.g. `Text::format(format_args!("{:05}", 42))`
+    pub fn format(args: fmt::Arguments) -> Self {
+        let mut text = Text::new();
+        fmt::write(&mut text, args).ok();
+        text
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.buf[..self.len]
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn is_truncated(&self) -> bool {
+        self.truncated
+    }
+
+    pub fn clear(&mut self) {
+        *self = Text::new();
+    }
+
+    /// Append a character code
+    pub fn push(&mut self, code: u8) {
+        if self.len < TEXT_CAPACITY {
+            self.buf[self.len] = code;
+            self.len += 1;
+        } else {
+            self.truncated = true;
+        }
+    }
+}
+
+impl Default for Text {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Text is mapped to the character ROM, missing characters become `?`
+impl fmt::Write for Text {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        for c in s.chars() {
+            self.push(charset::rom_code(c).unwrap_or(b'?'));
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Align {
+    Left,
+    Right,
+    Center,
+}
+
+/// Fixed area of a row
+#[derive(Clone, Copy)]
+pub struct Field {
+    pub col: u8,
+    pub row: u8,
+    pub width: u8,
+    pub align: Align,
+    pub ellipsis: Option<u8>,
+}
+
+impl Field {
+    pub const fn new(col: u8, row: u8, width: u8, align: Align) -> Self {
+        Field {
+            col,
+            row,
+            width,
+            align,
+            ellipsis: None,
+        }
+    }
+
+    /// Mark truncated text with the character code of an ellipsis
+    pub const fn with_ellipsis(mut self, code: u8) -> Self {
+        self.ellipsis = Some(code);
+        self
+    }
+
+    /// Align and truncate text to the field width
+    pub fn layout(&self, text: &Text) -> Text {
+        let width = (self.width as usize).min(TEXT_CAPACITY);
+        let mut field = Text::new();
+        field.len = width;
+        if text.len > width || text.truncated {
+            field.buf[..width].copy_from_slice(&text.buf[..width]);
+            field.truncated = true;
+            if let (Some(code), true) = (self.ellipsis, width > 0) {
+                field.buf[width - 1] = code;
+            }
+            return field;
+        }
+        let start = match self.align {
+            Align::Left => 0,
+            Align::Right => width - text.len,
+            Align::Center => (width - text.len) / 2,
+        };
+        field.buf[start..start + text.len].copy_from_slice(text.as_bytes());
+        field
+    }
+
+    /// Render text into the field of a frame
+    pub fn render_str(&self, frame: &mut Frame, text: &str) {
+        self.render_fmt(frame, format_args!("{}", text))
+    }
+
+    /// Render formatted text into the field of a frame
+    pub fn render_fmt(&self, frame: &mut Frame, args: fmt::Arguments) {
+        let field = self.layout(&Text::format(args));
+        frame.set_cursor(self.col as usize, self.row as usize);
+        field
+            .as_bytes()
+            .iter()
+            .for_each(|code| frame.write_byte(*code));
+    }
+
+    /// Write text into the field on the display
+    pub fn draw_str<E, I2C: Write<Error = E>>(
+        &self,
+        lcd: &RgbLCD,
+        i2c: &mut I2C,
+        text: &str,
+    ) -> Result<(), E> {
+        self.draw_fmt(lcd, i2c, format_args!("{}", text))
+    }
+
+    /// Write formatted text into the field on the display
+    pub fn draw_fmt<E, I2C: Write<Error = E>>(
+        &self,
+        lcd: &RgbLCD,
+        i2c: &mut I2C,
+        args: fmt::Arguments,
+    ) -> Result<(), E> {
+        let field = self.layout(&Text::format(args));
+        lcd.set_cursor(i2c, self.col, self.row)?;
+        lcd.write_bytes(i2c, field.as_bytes())
+    }
+}
+
+/// Fixed-point decimal, e.g. `Decimal::new(-52, 1)` is displayed as `-5.2`
+///
+/// Width, alignment and zero padding of the format specification are applied.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Decimal {
+    pub value: i32,
+    pub decimals: u8,
+}
+
+impl Decimal {
+    pub const fn new(value: i32, decimals: u8) -> Self {
+        Decimal { value, decimals }
+    }
+}
+
+impl fmt::Display for Decimal {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut digits = [b'0'; 12];
+        let mut magnitude = self.value.unsigned_abs();
+        let decimals = (self.decimals as usize).min(9);
+        let mut start = digits.len();
+        let mut position = 0;
+        // at least one digit before the point
+        while magnitude > 0 || position <= decimals {
+            if position == decimals && decimals > 0 {
+                start -= 1;
+                digits[start] = b'.';
+            }
+            start -= 1;
+            digits[start] = b'0' + (magnitude % 10) as u8;
+            magnitude /= 10;
+            position += 1;
+        }
+        // only ASCII digits and point
+        let text = core::str::from_utf8(&digits[start..]).unwrap_or("");
+        f.pad_integral(self.value >= 0, "", text)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::I2cMock;
+
+    fn layout(field: Field, args: fmt::Arguments) -> Text {
+        field.layout(&Text::format(args))
+    }
+
+    #[test]
+    fn align_in_field() {
+        let left = Field::new(0, 0, 6, Align::Left);
+        let right = Field::new(0, 0, 6, Align::Right);
+        let center = Field::new(0, 0, 6, Align::Center);
+        assert_eq!(layout(left, format_args!("ab")).as_bytes(), b"ab    ");
+        assert_eq!(layout(right, format_args!("ab")).as_bytes(), b"    ab");
+        assert_eq!(layout(center, format_args!("ab")).as_bytes(), b"  ab  ");
+    }
+
+    #[test]
+    fn truncate_with_ellipsis() {
+        let field = Field::new(0, 0, 5, Align::Right);
+        let text = layout(field, format_args!("{}", "Infrared"));
+        assert_eq!(text.as_bytes(), b"Infra");
+        assert!(text.is_truncated());
+        let text = layout(field.with_ellipsis(7), format_args!("{}", "Infrared"));
+        assert_eq!(text.as_bytes(), b"Infr\x07");
+    }
+
+    #[test]
+    fn numbers() {
+        let field = Field::new(0, 0, 7, Align::Right);
+        assert_eq!(
+            layout(field, format_args!("{:05}", 42)).as_bytes(),
+            b"  00042"
+        );
+        assert_eq!(
+            layout(field, format_args!("{}", Decimal::new(-52, 1))).as_bytes(),
+            b"   -5.2"
+        );
+        assert_eq!(
+            layout(field, format_args!("{}", Decimal::new(5, 2))).as_bytes(),
+            b"   0.05"
+        );
+        assert_eq!(
+            layout(field, format_args!("{:06}", Decimal::new(-1234, 2))).as_bytes(),
+            b" -12.34"
+        );
+        assert_eq!(
+            layout(field, format_args!("{:<6}|", Decimal::new(7, 0))).as_bytes(),
+            b"7     |"
+        );
+    }
+
+    #[test]
+    fn draw_updates_only_the_field() {
+        let lcd = RgbLCD::new();
+        let mut i2c = I2cMock::new();
+        let label = Field::new(0, 1, 8, Align::Left);
+        let value = Field::new(10, 1, 6, Align::Right);
+        label.draw_str(&lcd, &mut i2c, "Temp").unwrap();
+        value
+            .draw_fmt(&lcd, &mut i2c, format_args!("{}°C", Decimal::new(215, 1)))
+            .unwrap();
+        assert_eq!(&i2c.line(1)[..10], "Temp      ");
+        assert_eq!(i2c.ddram[0x4a..0x50], *b"21.5\xdfC");
+    }
+}
@@ -0,0 +1,567 @@
+//!
+//! Hierarchical menu for the 16x2 display
+//!
+//! Items are nested submenus, actions or values editable in place (integers,
+//! enumerations, booleans and RGB colors). The menu is driven by navigation
+//! events, e.g. from `EncoderInput`, and rendered as screen `Page`:
+//!
+//! * Up/Left and Down/Right move the selection arrow, Ok enters a submenu,
+//!   triggers an action or starts editing a value, Back returns to the parent
+//!   menu or exits the root menu.
+//! * While editing Up/Right increases and Down/Left decreases the value,
+//!   Ok confirms (for colors: moves on to the next component) and Back
+//!   cancels the edit.
+
+use core::fmt::{self, Write as _};
+
+use super::layout::Text;
+use super::screen::Page;
+use super::{Event, Frame, COLUMNS, ROWS};
+use crate::lcd::{charset, Color};
+
+/// Maximum nesting level of submenus
+pub const MAX_DEPTH: usize = 4;
+
+/// Increment of a color component per step
+const COLOR_STEP: u8 = 17;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Value<'a> {
+    Integer {
+        value: i32,
+        min: i32,
+        max: i32,
+        step: i32,
+    },
+    Enum {
+        index: usize,
+        options: &'a [&'a str],
+    },
+    Bool(bool),
+    Color(u8, u8, u8),
+}
+
+impl<'a> Value<'a> {
+    /// Backlight color of a color value
+    pub fn as_color(&self) -> Option<Color> {
+        match *self {
+            Value::Color(red, green, blue) => Some(Color::RGB(red, green, blue)),
+            _ => None,
+        }
+    }
+
+    fn adjust(&mut self, up: bool, component: usize) {
+        match self {
+            Value::Integer {
+                value,
+                min,
+                max,
+                step,
+            } => {
+                let next = if up {
+                    value.saturating_add(*step)
+                } else {
+                    value.saturating_sub(*step)
+                };
+                *value = next.max(*min).min(*max);
+            }
+            Value::Enum { index, options } => {
+                let count = options.len();
+                *index = if up {
+                    (*index + 1) % count
+                } else {
+                    (*index + count - 1) % count
+                };
+            }
+            Value::Bool(value) => *value = !*value,
+            Value::Color(red, green, blue) => {
+                let component = match component {
+                    0 => red,
+                    1 => green,
+                    _ => blue,
+                };
+                *component = if up {
+                    component.saturating_add(COLOR_STEP)
+                } else {
+                    component.saturating_sub(COLOR_STEP)
+                };
+            }
+        }
+    }
+}
+
+impl<'a> fmt::Display for Value<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Value::Integer { value, .. } => write!(f, "{}", value),
+            Value::Enum { index, options } => f.write_str(options[index]),
+            Value::Bool(true) => f.write_str("on"),
+            Value::Bool(false) => f.write_str("off"),
+            Value::Color(red, green, blue) => write!(f, "#{:02X}{:02X}{:02X}", red, green, blue),
+        }
+    }
+}
+
+pub enum Kind<'a> {
+    Submenu(&'a mut [Item<'a>]),
+    Action(u8),
+    Value(u8, Value<'a>),
+}
+
+pub struct Item<'a> {
+    label: &'a str,
+    kind: Kind<'a>,
+}
+
+impl<'a> Item<'a> {
+    pub fn submenu(label: &'a str, items: &'a mut [Item<'a>]) -> Self {
+        if items.is_empty() {
+            panic!("Submenu requires at least one item");
+        }
+        Item {
+            label,
+            kind: Kind::Submenu(items),
+        }
+    }
+
+    pub fn action(label: &'a str, id: u8) -> Self {
+        Item {
+            label,
+            kind: Kind::Action(id),
+        }
+    }
+
+    pub fn integer(label: &'a str, id: u8, value: i32, min: i32, max: i32, step: i32) -> Self {
+        Item {
+            label,
+            kind: Kind::Value(
+                id,
+                Value::Integer {
+                    value: value.max(min).min(max),
+                    min,
+                    max,
+                    step,
+                },
+            ),
+        }
+    }
+
+    pub fn choice(label: &'a str, id: u8, options: &'a [&'a str], index: usize) -> Self {
+        if index >= options.len() {
+            panic!("Choice index must be in range of options");
+        }
+        Item {
+            label,
+            kind: Kind::Value(id, Value::Enum { index, options }),
+        }
+    }
+
+    pub fn boolean(label: &'a str, id: u8, value: bool) -> Self {
+        Item {
+            label,
+            kind: Kind::Value(id, Value::Bool(value)),
+        }
+    }
+
+    pub fn color(label: &'a str, id: u8, red: u8, green: u8, blue: u8) -> Self {
+        Item {
+            label,
+            kind: Kind::Value(id, Value::Color(red, green, blue)),
+        }
+    }
+
+    pub fn label(&self) -> &'a str {
+        self.label
+    }
+
+    pub fn kind(&self) -> &Kind<'a> {
+        &self.kind
+    }
+}
+
+/// Outcome of handling an input event
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum MenuEvent {
+    /// An action item was selected
+    Action(u8),
+    /// A value was edited and confirmed
+    Changed(u8),
+    /// Back was pressed in the root menu
+    Exit,
+}
+
+struct Edit<'a> {
+    backup: Value<'a>,
+    component: usize,
+}
+
+pub struct Menu<'a> {
+    root: &'a mut [Item<'a>],
+    path: [usize; MAX_DEPTH],
+    depth: usize,
+    selected: usize,
+    top: usize,
+    edit: Option<Edit<'a>>,
+}
+
+impl<'a> Menu<'a> {
+    pub fn new(root: &'a mut [Item<'a>]) -> Self {
+        if root.is_empty() {
+            panic!("Menu requires at least one item");
+        }
+        if nesting(root) > MAX_DEPTH {
+            panic!("Menu nesting exceeds MAX_DEPTH");
+        }
+        Menu {
+            root,
+            path: [0; MAX_DEPTH],
+            depth: 0,
+            selected: 0,
+            top: 0,
+            edit: None,
+        }
+    }
+
+    /// Nesting level of the current (sub)menu, the root menu is 0
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+
+    /// Index of the selected item in the current (sub)menu
+    pub fn selected(&self) -> usize {
+        self.selected
+    }
+
+    pub fn is_editing(&self) -> bool {
+        self.edit.is_some()
+    }
+
+    /// Current value of the item with id, including edits in progress
+    pub fn value(&self, id: u8) -> Option<Value<'a>> {
+        find_value(self.root, id)
+    }
+
+    /// Return to the first item of the root menu, cancelling any edit
+    pub fn reset(&mut self) {
+        self.cancel_edit();
+        self.depth = 0;
+        self.selected = 0;
+        self.top = 0;
+    }
+
+    /// Process an input event
+    pub fn handle(&mut self, event: Event) -> Option<MenuEvent> {
+        if self.edit.is_some() {
+            return self.handle_edit(event);
+        }
+        let count = self.items().len();
+        match event {
+            Event::Up | Event::Left => self.select((self.selected + count - 1) % count),
+            Event::Down | Event::Right => self.select((self.selected + 1) % count),
+            Event::Ok => {
+                let selected = self.selected;
+                match self.items_mut()[selected].kind {
+                    Kind::Submenu(_) => {
+                        // the nesting is checked by new
+                        self.path[self.depth] = selected;
+                        self.depth += 1;
+                        self.selected = 0;
+                        self.top = 0;
+                    }
+                    Kind::Action(id) => return Some(MenuEvent::Action(id)),
+                    Kind::Value(_, value) => {
+                        self.edit = Some(Edit {
+                            backup: value,
+                            component: 0,
+                        })
+                    }
+                }
+            }
+            Event::Back => {
+                if self.depth == 0 {
+                    return Some(MenuEvent::Exit);
+                }
+                self.depth -= 1;
+                self.top = 0;
+                self.select(self.path[self.depth]);
+            }
+        }
+        None
+    }
+
+    fn handle_edit(&mut self, event: Event) -> Option<MenuEvent> {
+        let selected = self.selected;
+        let component = self.edit.as_ref().map_or(0, |edit| edit.component);
+        let (id, value) = match self.items_mut()[selected].kind {
+            Kind::Value(id, ref mut value) => (id, value),
+            _ => unreachable!(),
+        };
+        match event {
+            Event::Up | Event::Right => value.adjust(true, component),
+            Event::Down | Event::Left => value.adjust(false, component),
+            Event::Ok => {
+                if let Value::Color(..) = value {
+                    if component < 2 {
+                        if let Some(edit) = self.edit.as_mut() {
+                            edit.component += 1;
+                        }
+                        return None;
+                    }
+                }
+                self.edit = None;
+                return Some(MenuEvent::Changed(id));
+            }
+            Event::Back => self.cancel_edit(),
+        }
+        None
+    }
+
+    fn cancel_edit(&mut self) {
+        if let Some(edit) = self.edit.take() {
+            let selected = self.selected;
+            if let Kind::Value(_, ref mut value) = self.items_mut()[selected].kind {
+                *value = edit.backup;
+            }
+        }
+    }
+
+    fn select(&mut self, index: usize) {
+        self.selected = index;
+        if index < self.top {
+            self.top = index;
+        } else if index >= self.top + ROWS {
+            self.top = index + 1 - ROWS;
+        }
+    }
+
+    fn items(&self) -> &[Item<'a>] {
+        let mut items: &[Item<'a>] = self.root;
+        for level in 0..self.depth {
+            items = match items[self.path[level]].kind {
+                Kind::Submenu(ref submenu) => submenu,
+                _ => unreachable!(),
+            };
+        }
+        items
+    }
+
+    fn items_mut(&mut self) -> &mut [Item<'a>] {
+        let mut items: &mut [Item<'a>] = self.root;
+        for level in 0..self.depth {
+            items = match items[self.path[level]].kind {
+                Kind::Submenu(ref mut submenu) => submenu,
+                _ => unreachable!(),
+            };
+        }
+        items
+    }
+}
+
+impl<'a> Page for Menu<'a> {
+    fn render(&mut self, frame: &mut Frame) {
+        let items = self.items();
+        if let Some(ref edit) = self.edit {
+            let item = &items[self.selected];
+            frame.set_cursor(0, 0);
+            write_clipped(frame, item.label, COLUMNS);
+            frame.set_cursor(0, 1);
+            match item.kind {
+                Kind::Value(_, Value::Color(red, green, blue)) => {
+                    for (component, (name, level)) in
+                        [('R', red), ('G', green), ('B', blue)].iter().enumerate()
+                    {
+                        let marker = if component == edit.component {
+                            '→'
+                        } else {
+                            ' '
+                        };
+                        write!(frame, "{}{}{:03}", marker, name, level).ok();
+                    }
+                }
+                Kind::Value(_, value) => {
+                    write!(frame, "→{}", value).ok();
+                }
+                _ => (),
+            }
+            return;
+        }
+        for row in 0..ROWS {
+            let index = self.top + row;
+            if index >= items.len() {
+                break;
+            }
+            let item = &items[index];
+            let mut suffix = Text::new();
+            match item.kind {
+                Kind::Submenu(_) => suffix.write_str(">").ok(),
+                Kind::Action(_) => None,
+                Kind::Value(_, value) => write!(suffix, "{}", value).ok(),
+            };
+            let suffix = &suffix.as_bytes()[..suffix.len().min(COLUMNS - 2)];
+            frame.set_cursor(0, row);
+            frame.write_byte(if index == self.selected {
+                charset::rom_code('→').unwrap_or(b'>')
+            } else {
+                b' '
+            });
+            let width = COLUMNS - 1 - suffix.len() - if suffix.is_empty() { 0 } else { 1 };
+            write_clipped(frame, item.label, width);
+            frame.set_cursor(COLUMNS - suffix.len(), row);
+            suffix.iter().for_each(|code| frame.write_byte(*code));
+        }
+    }
+}
+
+fn find_value<'a>(items: &[Item<'a>], id: u8) -> Option<Value<'a>> {
+    items.iter().find_map(|item| match item.kind {
+        Kind::Submenu(ref submenu) => find_value(submenu, id),
+        Kind::Value(item_id, value) if item_id == id => Some(value),
+        _ => None,
+    })
+}
+
+/// Number of submenu levels below items
+fn nesting(items: &[Item]) -> usize {
+    items
+        .iter()
+        .map(|item| match item.kind {
+            Kind::Submenu(ref submenu) => 1 + nesting(submenu),
+            _ => 0,
+        })
+        .max()
+        .unwrap_or(0)
+}
+
+fn write_clipped(frame: &mut Frame, text: &str, width: usize) {
+    for c in text.chars().take(width) {
+        frame.write_byte(charset::rom_code(c).unwrap_or(b'?'));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MODES: [&str; 3] = ["RC5", "NEC", "SIRC"];
+
+    fn lines(menu: &mut Menu) -> [[u8; COLUMNS]; ROWS] {
+        let mut frame = Frame::new();
+        menu.render(&mut frame);
+        [*frame.row(0), *frame.row(1)]
+    }
+
+    #[test]
+    fn navigate_submenu_and_edit_integer() {
+        let mut infrared = [
+            Item::choice("Mode", 1, &MODES, 0),
+            Item::integer("Address", 2, 0, 0, 31, 1),
+        ];
+        let mut root = [
+            Item::submenu("Infrared", &mut infrared),
+            Item::boolean("Sound", 3, true),
+            Item::action("Reset", 4),
+        ];
+        let mut menu = Menu::new(&mut root);
+
+        assert_eq!(&lines(&mut menu)[0], b"\x7eInfrared      >");
+        assert_eq!(&lines(&mut menu)[1], b" Sound        on");
+
+        // scroll down to the action, wrap around to the top
+        assert_eq!(menu.handle(Event::Right), None);
+        assert_eq!(menu.handle(Event::Right), None);
+        assert_eq!(&lines(&mut menu)[1], b"\x7eReset          ");
+        assert_eq!(menu.handle(Event::Ok), Some(MenuEvent::Action(4)));
+        menu.handle(Event::Right);
+        assert_eq!(menu.selected(), 0);
+
+        // enter submenu and edit the address
+        menu.handle(Event::Ok);
+        assert_eq!(menu.depth(), 1);
+        menu.handle(Event::Down);
+        menu.handle(Event::Ok);
+        assert!(menu.is_editing());
+        menu.handle(Event::Left); // clamped at min
+        menu.handle(Event::Right);
+        menu.handle(Event::Right);
+        assert_eq!(&lines(&mut menu)[1], b"\x7e2              ");
+        assert_eq!(menu.handle(Event::Ok), Some(MenuEvent::Changed(2)));
+        match menu.value(2) {
+            Some(Value::Integer { value, .. }) => assert_eq!(value, 2),
+            _ => panic!("integer expected"),
+        }
+
+        // back to root, select the submenu again, exit
+        assert_eq!(menu.handle(Event::Back), None);
+        assert_eq!(menu.depth(), 0);
+        assert_eq!(menu.selected(), 0);
+        assert_eq!(menu.handle(Event::Back), Some(MenuEvent::Exit));
+    }
+
+    #[test]
+    fn cancel_restores_value() {
+        let mut root = [Item::choice("Mode", 1, &MODES, 0)];
+        let mut menu = Menu::new(&mut root);
+        menu.handle(Event::Ok);
+        menu.handle(Event::Left);
+        assert_eq!(
+            menu.value(1),
+            Some(Value::Enum {
+                index: 2,
+                options: &MODES
+            })
+        );
+        menu.handle(Event::Back);
+        assert!(!menu.is_editing());
+        assert_eq!(
+            menu.value(1),
+            Some(Value::Enum {
+                index: 0,
+                options: &MODES
+            })
+        );
+    }
+
+    #[test]
+    fn edit_color_component_wise() {
+        let mut root = [Item::color("Backlight", 7, 0, 0, 255)];
+        let mut menu = Menu::new(&mut root);
+        menu.handle(Event::Ok);
+        menu.handle(Event::Right);
+        assert_eq!(menu.handle(Event::Ok), None);
+        menu.handle(Event::Right);
+        menu.handle(Event::Right);
+        assert_eq!(menu.handle(Event::Ok), None);
+        menu.handle(Event::Right); // saturated
+        assert_eq!(&lines(&mut menu)[1], b" R017 G034\x7eB255 ");
+        assert_eq!(menu.handle(Event::Ok), Some(MenuEvent::Changed(7)));
+        assert_eq!(menu.value(7), Some(Value::Color(17, 34, 255)));
+        assert_eq!(&lines(&mut menu)[0], b"\x7eBacklig #1122FF");
+    }
+
+    #[test]
+    #[should_panic(expected = "MAX_DEPTH")]
+    fn too_deep_nesting_is_rejected() {
+        let mut level5 = [Item::action("Deep", 1)];
+        let mut level4 = [Item::submenu("5", &mut level5)];
+        let mut level3 = [Item::submenu("4", &mut level4)];
+        let mut level2 = [Item::submenu("3", &mut level3)];
+        let mut level1 = [Item::submenu("2", &mut level2)];
+        let mut root = [Item::submenu("1", &mut level1)];
+        Menu::new(&mut root);
+    }
+
+    #[test]
+    fn deepest_nesting_is_accepted() {
+        let mut level4 = [Item::action("Deep", 1)];
+        let mut level3 = [Item::submenu("4", &mut level4)];
+        let mut level2 = [Item::submenu("3", &mut level3)];
+        let mut level1 = [Item::submenu("2", &mut level2)];
+        let mut root = [Item::submenu("1", &mut level1)];
+        let mut menu = Menu::new(&mut root);
+        for _ in 0..MAX_DEPTH {
+            menu.handle(Event::Ok);
+        }
+        assert_eq!(menu.depth(), MAX_DEPTH);
+        assert_eq!(menu.handle(Event::Ok), Some(MenuEvent::Action(1)));
+    }
+}
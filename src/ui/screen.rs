@@ -0,0 +1,223 @@
+//!
+//! Multi-page screen manager
+//!
+//! Registered pages are shown in turn, rotating after a number of ticks.
+//! A pinned status line overlays one row of every page. An alert page preempts
+//! the rotation and sets its own backlight color until it is cleared.
+//!
+//! Only rows that changed since the last tick are transferred to the display.
+
+use embedded_hal::blocking::i2c::Write;
+
+use super::Frame;
+use crate::lcd::{Color, RgbLCD};
+
+/// Content of a screen page
+///
+/// Closures taking a `&mut Frame` are pages as well.
+pub trait Page {
+    /// Draw the page, the frame is blank on entry
+    fn render(&mut self, frame: &mut Frame);
+}
+
+impl<F: FnMut(&mut Frame)> Page for F {
+    fn render(&mut self, frame: &mut Frame) {
+        self(frame)
+    }
+}
+
+pub struct ScreenManager<'a, 'p> {
+    pages: &'a mut [&'p mut dyn Page],
+    current: usize,
+    ticks_per_page: u32,
+    ticks: u32,
+    status: Option<(&'p mut dyn Page, usize)>,
+    alert: Option<(&'p mut dyn Page, Color)>,
+    color: Color,
+    color_changed: bool,
+    shown: Option<Frame>,
+}
+
+impl<'a, 'p> ScreenManager<'a, 'p> {
+    /// Create a screen manager
+    ///
+    /// Args:
+    /// * pages - at least one page
+    /// * ticks_per_page - rotate to the next page after that many ticks, 0 disables rotation
+    /// * color - backlight color while no alert is raised
+    pub fn new(pages: &'a mut [&'p mut dyn Page], ticks_per_page: u32, color: Color) -> Self {
+        if pages.is_empty() {
+            panic!("Screen manager requires at least one page");
+        }
+        ScreenManager {
+            pages,
+            current: 0,
+            ticks_per_page,
+            ticks: 0,
+            status: None,
+            alert: None,
+            color,
+            color_changed: true,
+            shown: None,
+        }
+    }
+
+    /// Pin a status line rendered by status (its first row) onto row of every page
+    pub fn with_status_line(mut self, status: &'p mut dyn Page, row: usize) -> Self {
+        if row >= super::ROWS {
+            panic!("Status line must be in range 0..1");
+        }
+        self.status = Some((status, row));
+        self
+    }
+
+    /// Index of the page in rotation
+    pub fn current(&self) -> usize {
+        self.current
+    }
+
+    /// Jump to a page and restart the rotation timer
+    pub fn show(&mut self, index: usize) {
+        self.current = index % self.pages.len();
+        self.ticks = 0;
+    }
+
+    pub fn next(&mut self) {
+        self.show(self.current + 1);
+    }
+
+    pub fn previous(&mut self) {
+        self.show(self.current + self.pages.len() - 1);
+    }
+
+    /// Set the backlight color used while no alert is raised
+    pub fn set_color(&mut self, color: Color) {
+        self.color = color;
+        self.color_changed = true;
+    }
+
+    /// Show an alert page instead of the rotating pages
+    pub fn raise_alert(&mut self, page: &'p mut dyn Page, color: Color) {
+        self.alert = Some((page, color));
+        self.color_changed = true;
+    }
+
+    /// Resume rotation, returns the alert page if any
+    pub fn clear_alert(&mut self) -> Option<&'p mut dyn Page> {
+        self.color_changed = true;
+        self.alert.take().map(|(page, _)| page)
+    }
+
+    pub fn has_alert(&self) -> bool {
+        self.alert.is_some()
+    }
+
+    /// Redraw all rows with the next render, e.g. if the display was used otherwise
+    pub fn invalidate(&mut self) {
+        self.shown = None;
+        self.color_changed = true;
+    }
+
+    /// Advance the rotation and render, to be called from a periodic tick
+    pub fn tick<E, I2C: Write<Error = E>>(&mut self, lcd: &RgbLCD, i2c: &mut I2C) -> Result<(), E> {
+        if self.alert.is_none() && self.ticks_per_page > 0 {
+            self.ticks += 1;
+            if self.ticks >= self.ticks_per_page {
+                self.next();
+            }
+        }
+        self.render(lcd, i2c)
+    }
+
+    /// Render the alert or current page without advancing the rotation
+    pub fn render<E, I2C: Write<Error = E>>(
+        &mut self,
+        lcd: &RgbLCD,
+        i2c: &mut I2C,
+    ) -> Result<(), E> {
+        let mut frame = Frame::new();
+        let color = match self.alert {
+            Some((ref mut alert, color)) => {
+                alert.render(&mut frame);
+                color
+            }
+            None => {
+                self.pages[self.current].render(&mut frame);
+                if let Some((ref mut status, row)) = self.status {
+                    let mut status_frame = Frame::new();
+                    status.render(&mut status_frame);
+                    frame.set_cursor(0, row);
+                    for code in status_frame.row(0) {
+                        frame.write_byte(*code);
+                    }
+                }
+                self.color
+            }
+        };
+        if self.color_changed {
+            lcd.set_color(i2c, color)?;
+            self.color_changed = false;
+        }
+        match self.shown {
+            Some(ref shown) => frame.flush_changes(lcd, i2c, shown)?,
+            None => frame.flush(lcd, i2c)?,
+        }
+        self.shown = Some(frame);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::I2cMock;
+    use core::fmt::Write as _;
+
+    #[test]
+    fn pages_rotate_and_status_line_is_pinned() {
+        let mut first = |frame: &mut Frame| write!(frame, "first").unwrap();
+        let mut second = |frame: &mut Frame| write!(frame, "second").unwrap();
+        let mut status = |frame: &mut Frame| write!(frame, "12:00").unwrap();
+        let mut pages: [&mut dyn Page; 2] = [&mut first, &mut second];
+        let mut screen =
+            ScreenManager::new(&mut pages, 2, Color::White).with_status_line(&mut status, 1);
+        let lcd = RgbLCD::new();
+        let mut i2c = I2cMock::new();
+
+        screen.tick(&lcd, &mut i2c).unwrap();
+        assert_eq!(i2c.line(0), "first           ");
+        assert_eq!(i2c.line(1), "12:00           ");
+        assert_eq!(i2c.color(), (255, 255, 255));
+
+        let writes = i2c.writes.len();
+        screen.tick(&lcd, &mut i2c).unwrap();
+        assert_eq!(screen.current(), 1);
+        assert_eq!(i2c.line(0), "second          ");
+        // only the page row is transferred
+        assert_eq!(i2c.writes.len(), writes + 2);
+
+        screen.previous();
+        screen.render(&lcd, &mut i2c).unwrap();
+        assert_eq!(i2c.line(0), "first           ");
+    }
+
+    #[test]
+    fn alert_preempts_rotation_and_restores_color() {
+        let mut page = |frame: &mut Frame| write!(frame, "page").unwrap();
+        let mut alert = |frame: &mut Frame| write!(frame, "ALERT").unwrap();
+        let mut pages: [&mut dyn Page; 1] = [&mut page];
+        let mut screen = ScreenManager::new(&mut pages, 1, Color::Green);
+        let lcd = RgbLCD::new();
+        let mut i2c = I2cMock::new();
+
+        screen.raise_alert(&mut alert, Color::Red);
+        screen.tick(&lcd, &mut i2c).unwrap();
+        assert_eq!(i2c.line(0), "ALERT           ");
+        assert_eq!(i2c.color(), (255, 0, 0));
+
+        assert!(screen.clear_alert().is_some());
+        screen.tick(&lcd, &mut i2c).unwrap();
+        assert_eq!(i2c.line(0), "page            ");
+        assert_eq!(i2c.color(), (0, 255, 0));
+    }
+}
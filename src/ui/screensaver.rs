@@ -0,0 +1,291 @@
+//!
+//! Inactivity screensaver
+//!
+//! After `dim_after` idle ticks the backlight fades to a dim level, after
+//! `sleep_after` idle ticks the display is switched off or shows a clock
+//! moving slowly across the display. Any input - navigation events, IR codes,
+//! UART bytes or button presses - is reported with `wake`, which restores the
+//! display and the backlight color.
+//!
+//! The screensaver owns the backlight color, set it with `set_color` instead
+//! of `RgbLCD::set_color`.
+//!
+//! ```ignore
+//! if let Some(event) = encoder.poll() {
+//!     if !screensaver.wake(&mut lcd, &mut i2c)? {
+//!         menu.handle(event);
+//!     }
+//! }
+//! screensaver.tick(&mut lcd, &mut i2c)?;
+//! ```
+
+use core::fmt::Write as _;
+
+use embedded_hal::blocking::i2c::Write;
+
+use super::{Frame, COLUMNS, ROWS};
+use crate::lcd::{Color, RgbLCD};
+
+/// Width of the clock `hh:mm`
+const CLOCK_WIDTH: usize = 5;
+
+/// Presentation once asleep
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum SleepMode {
+    /// Switch the display off, the backlight stays dimmed
+    DisplayOff,
+    /// Show the clock, moving to another position after that many ticks
+    Clock { move_after: u32 },
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum State {
+    Awake,
+    /// Fading to or at the dim level
+    Dimmed,
+    Asleep,
+}
+
+pub struct Screensaver {
+    dim_after: u32,
+    sleep_after: u32,
+    dim_level: u8,
+    fade_step: u8,
+    mode: SleepMode,
+    state: State,
+    idle: u32,
+    level: u8,
+    color: Color,
+    color_changed: bool,
+    clock: (u8, u8),
+    clock_position: usize,
+    clock_ticks: u32,
+    clock_changed: bool,
+}
+
+impl Screensaver {
+    /// Create a screensaver
+    ///
+    /// Args:
+    /// * dim_after - idle ticks until the backlight is dimmed, 0 never dims
+    /// * sleep_after - idle ticks until sleep, 0 never sleeps
+    /// * dim_level - brightness when dimmed, 255 is full brightness
+    /// * mode - presentation once asleep
+    pub fn new(dim_after: u32, sleep_after: u32, dim_level: u8, mode: SleepMode) -> Self {
+        Screensaver {
+            dim_after,
+            sleep_after,
+            dim_level,
+            fade_step: 255,
+            mode,
+            state: State::Awake,
+            idle: 0,
+            level: 255,
+            color: Color::Green,
+            color_changed: false,
+            clock: (0, 0),
+            clock_position: 0,
+            clock_ticks: 0,
+            clock_changed: false,
+        }
+    }
+
+    /// Fade by step brightness levels per tick instead of dimming at once
+    pub fn with_fade_step(mut self, step: u8) -> Self {
+        self.fade_step = step.max(1);
+        self
+    }
+
+    pub fn state(&self) -> State {
+        self.state
+    }
+
+    /// Current brightness, 255 is full brightness
+    pub fn level(&self) -> u8 {
+        self.level
+    }
+
+    /// Set the backlight color, applied with the next tick at the current brightness
+    pub fn set_color(&mut self, color: Color) {
+        self.color = color;
+        self.color_changed = true;
+    }
+
+    /// Set the time shown by the clock
+    pub fn set_clock(&mut self, hours: u8, minutes: u8) {
+        self.clock_changed |= self.clock != (hours, minutes);
+        self.clock = (hours, minutes);
+    }
+
+    /// Report input activity and restore the display
+    ///
+    /// Returns
+    /// * true if the screensaver was asleep, i.e. the display content must be
+    ///   redrawn in clock mode and the input should be discarded
+    pub fn wake<E, I2C: Write<Error = E>>(
+        &mut self,
+        lcd: &mut RgbLCD,
+        i2c: &mut I2C,
+    ) -> Result<bool, E> {
+        self.idle = 0;
+        let state = self.state;
+        self.state = State::Awake;
+        if state == State::Asleep && self.mode == SleepMode::DisplayOff {
+            lcd.switch_display_on(i2c)?;
+        }
+        if state != State::Awake {
+            self.level = 255;
+            self.color_changed = false;
+            lcd.set_color(i2c, self.color)?;
+        }
+        Ok(state == State::Asleep)
+    }
+
+    /// Count an idle tick, dim and sleep when due
+    pub fn tick<E, I2C: Write<Error = E>>(
+        &mut self,
+        lcd: &mut RgbLCD,
+        i2c: &mut I2C,
+    ) -> Result<(), E> {
+        self.idle = self.idle.saturating_add(1);
+        if self.state == State::Awake && self.dim_after > 0 && self.idle >= self.dim_after {
+            self.state = State::Dimmed;
+        }
+        if self.state != State::Awake && self.dim_after > 0 && self.level > self.dim_level {
+            self.level = self
+                .level
+                .saturating_sub(self.fade_step)
+                .max(self.dim_level);
+            self.color_changed = true;
+        }
+        if self.color_changed {
+            lcd.set_color(i2c, dim(self.color, self.level))?;
+            self.color_changed = false;
+        }
+        // sleeping does not require dimming first
+        if self.state != State::Asleep && self.sleep_after > 0 && self.idle >= self.sleep_after {
+            self.state = State::Asleep;
+            return match self.mode {
+                SleepMode::DisplayOff => lcd.switch_display_off(i2c),
+                SleepMode::Clock { .. } => {
+                    self.clock_ticks = 0;
+                    self.draw_clock(lcd, i2c)
+                }
+            };
+        }
+        if let (State::Asleep, SleepMode::Clock { move_after }) = (self.state, self.mode) {
+            self.clock_ticks += 1;
+            if move_after > 0 && self.clock_ticks >= move_after {
+                self.clock_ticks = 0;
+                self.clock_position += 1;
+                self.clock_changed = true;
+            }
+            if self.clock_changed {
+                self.draw_clock(lcd, i2c)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn draw_clock<E, I2C: Write<Error = E>>(
+        &mut self,
+        lcd: &RgbLCD,
+        i2c: &mut I2C,
+    ) -> Result<(), E> {
+        // walk through the columns, alternating the rows
+        let positions = COLUMNS - CLOCK_WIDTH + 1;
+        let col = (self.clock_position * 3) % positions;
+        let row = self.clock_position % ROWS;
+        let mut frame = Frame::new();
+        frame.set_cursor(col, row);
+        write!(frame, "{:02}:{:02}", self.clock.0, self.clock.1).ok();
+        self.clock_changed = false;
+        frame.flush(lcd, i2c)
+    }
+}
+
+/// Scale the color components by level / 255
+fn dim(color: Color, level: u8) -> Color {
+    let (red, green, blue) = color.rgb();
+    let scale = |component: u8| (component as u16 * level as u16 / 255) as u8;
+    Color::RGB(scale(red), scale(green), scale(blue))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::I2cMock;
+
+    #[test]
+    fn fade_switch_off_and_wake() {
+        let mut lcd = RgbLCD::new();
+        let mut i2c = I2cMock::new();
+        lcd.switch_display_on(&mut i2c).unwrap();
+        let mut saver = Screensaver::new(2, 5, 55, SleepMode::DisplayOff).with_fade_step(100);
+        saver.set_color(Color::White);
+
+        saver.tick(&mut lcd, &mut i2c).unwrap();
+        assert_eq!(saver.state(), State::Awake);
+        assert_eq!(i2c.color(), (255, 255, 255));
+
+        saver.tick(&mut lcd, &mut i2c).unwrap();
+        assert_eq!(saver.state(), State::Dimmed);
+        assert_eq!(i2c.color(), (155, 155, 155));
+        saver.tick(&mut lcd, &mut i2c).unwrap();
+        saver.tick(&mut lcd, &mut i2c).unwrap();
+        assert_eq!(i2c.color(), (55, 55, 55));
+
+        saver.tick(&mut lcd, &mut i2c).unwrap();
+        assert_eq!(saver.state(), State::Asleep);
+        assert_eq!(i2c.display_control & 0x04, 0x00);
+
+        assert!(saver.wake(&mut lcd, &mut i2c).unwrap());
+        assert_eq!(saver.state(), State::Awake);
+        assert_eq!(i2c.display_control & 0x04, 0x04);
+        assert_eq!(i2c.color(), (255, 255, 255));
+        // further input is not swallowed
+        assert!(!saver.wake(&mut lcd, &mut i2c).unwrap());
+    }
+
+    #[test]
+    fn clock_moves_while_asleep() {
+        let mut lcd = RgbLCD::new();
+        let mut i2c = I2cMock::new();
+        let mut saver = Screensaver::new(1, 2, 0, SleepMode::Clock { move_after: 3 });
+        saver.set_clock(7, 5);
+        saver.tick(&mut lcd, &mut i2c).unwrap();
+        saver.tick(&mut lcd, &mut i2c).unwrap();
+        assert_eq!(saver.state(), State::Asleep);
+        assert_eq!(i2c.line(0), "07:05           ");
+
+        saver.set_clock(7, 6);
+        saver.tick(&mut lcd, &mut i2c).unwrap();
+        assert_eq!(i2c.line(0), "07:06           ");
+        saver.tick(&mut lcd, &mut i2c).unwrap();
+        saver.tick(&mut lcd, &mut i2c).unwrap();
+        assert_eq!(i2c.line(0), "                ");
+        assert_eq!(i2c.line(1), "   07:06        ");
+    }
+
+    #[test]
+    fn sleep_without_dimming() {
+        let mut lcd = RgbLCD::new();
+        let mut i2c = I2cMock::new();
+        lcd.switch_display_on(&mut i2c).unwrap();
+        let mut saver = Screensaver::new(0, 3, 55, SleepMode::DisplayOff);
+        saver.set_color(Color::White);
+        saver.tick(&mut lcd, &mut i2c).unwrap();
+        saver.tick(&mut lcd, &mut i2c).unwrap();
+        assert_eq!(saver.state(), State::Awake);
+
+        saver.tick(&mut lcd, &mut i2c).unwrap();
+        assert_eq!(saver.state(), State::Asleep);
+        assert_eq!(i2c.display_control & 0x04, 0x00);
+        saver.tick(&mut lcd, &mut i2c).unwrap();
+        assert_eq!(saver.level(), 255);
+        assert_eq!(i2c.color(), (255, 255, 255));
+
+        assert!(saver.wake(&mut lcd, &mut i2c).unwrap());
+        assert_eq!(i2c.display_control & 0x04, 0x04);
+    }
+}